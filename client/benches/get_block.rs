@@ -0,0 +1,117 @@
+//! Compares fetching a large block through the buffered `Queryable` path
+//! with the streaming `RpcApi::get_block` path.
+//!
+//! A local HTTP server answers every `getblock` request with the same block,
+//! so only the client side is measured. Run with `cargo bench`.
+
+extern crate bitcoincore_rpc;
+extern crate serde_json;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+use bitcoincore_rpc::bitcoin::consensus::encode::serialize;
+use bitcoincore_rpc::bitcoin::hashes::hex::ToHex;
+use bitcoincore_rpc::bitcoin::util::hash::BitcoinHash;
+use bitcoincore_rpc::bitcoin::{Block, Network, OutPoint, TxIn, TxOut};
+use bitcoincore_rpc::{Auth, Client, RpcApi};
+
+const ITERATIONS: u32 = 20;
+
+/// A block of about 2 MB with many small transactions.
+fn large_block() -> Block {
+    let mut block = genesis_block(Network::Regtest);
+    let template = block.txdata[0].clone();
+    for i in 0..10_000u32 {
+        let mut tx = template.clone();
+        tx.input = vec![TxIn {
+            previous_output: OutPoint {
+                txid: template.txid(),
+                vout: i,
+            },
+            ..template.input[0].clone()
+        }];
+        tx.output = vec![TxOut {
+            value: i as u64,
+            script_pubkey: template.output[0].script_pubkey.clone(),
+        }];
+        block.txdata.push(tx);
+    }
+    block.header.merkle_root = block.merkle_root();
+    block
+}
+
+fn serve(mut stream: TcpStream, hex: Arc<String>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    loop {
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                return;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let lower = line.to_ascii_lowercase();
+            if let Some(len) = lower.strip_prefix("content-length:") {
+                content_length = len.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body).unwrap();
+        let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        let response = format!(r#"{{"result":"{}","error":null,"id":{}}}"#, hex, request["id"]);
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            response.len()
+        )
+        .unwrap();
+        stream.write_all(response.as_bytes()).unwrap();
+    }
+}
+
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_iter = start.elapsed() / ITERATIONS;
+    println!("{:<24} {:>10.2} ms/iter", name, per_iter.as_secs_f64() * 1000.0);
+}
+
+fn main() {
+    let block = large_block();
+    let hex = Arc::new(serialize(&block).to_hex());
+    println!("block size: {} bytes", hex.len() / 2);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let hex = hex.clone();
+            thread::spawn(move || serve(stream.unwrap(), hex));
+        }
+    });
+    thread::sleep(Duration::from_millis(10));
+
+    let client = Client::new(url, Auth::None).unwrap();
+    let hash = block.bitcoin_hash();
+
+    bench("Queryable for Block", || {
+        let fetched: Block = client.get_by_id(&hash).unwrap();
+        assert_eq!(fetched.txdata.len(), block.txdata.len());
+    });
+    bench("get_block (streaming)", || {
+        let fetched = client.get_block(&hash).unwrap();
+        assert_eq!(fetched.txdata.len(), block.txdata.len());
+    });
+}
@@ -0,0 +1,466 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Caching of immutable RPC results.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::slice;
+use std::sync::Mutex;
+
+use bitcoin;
+use bitcoin::consensus::encode::Decodable;
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::{sha256, Hash};
+use serde;
+use serde_json;
+
+use client::{Result, RpcApi};
+use error::Error;
+
+/// An [RpcApi] wrapper that caches results that can't change anymore.
+///
+/// Only the raw forms of the following calls are cached, the verbose forms
+/// contain the number of confirmations:
+///
+/// - `getblock` with verbosity 0,
+/// - `getblockheader` with `verbose=false`,
+/// - `getrawtransaction` with `verbose=false` and a block hash,
+/// - `getblockfilter`,
+/// - `getblockhash`.
+///
+/// A result is only cached once its block has at least the configured
+/// number of confirmations, which needs one extra call on every cache miss,
+/// or one extra batch for the misses of a batch.
+/// Results of stale blocks are never cached. All other calls are passed
+/// through.
+///
+/// Unlike the others, the result of `getblockhash` is looked up by height and
+/// changes if a reorg goes deeper than the configured number of
+/// confirmations. It is only cached in memory, where it assumes there is no
+/// such reorg, see [CachingClient::clear_memory].
+pub struct CachingClient<C> {
+    rpc: C,
+    memory: Mutex<Lru>,
+    disk: Option<PathBuf>,
+    min_confirmations: u32,
+}
+
+impl<C: RpcApi> CachingClient<C> {
+    /// Cache up to about `capacity` bytes of results in memory.
+    ///
+    /// By default results are cached once their block has 6 confirmations.
+    pub fn new(rpc: C, capacity: usize) -> CachingClient<C> {
+        CachingClient {
+            rpc,
+            memory: Mutex::new(Lru::new(capacity)),
+            disk: None,
+            min_confirmations: 6,
+        }
+    }
+
+    /// Also cache results as files in `dir`, which is created if needed.
+    /// Results of `getblockhash` are not cached on disk.
+    ///
+    /// The directory can be shared between runs, but not between nodes of
+    /// different networks.
+    pub fn disk_cache(mut self, dir: PathBuf) -> Result<CachingClient<C>> {
+        fs::create_dir_all(&dir)?;
+        self.disk = Some(dir);
+        Ok(self)
+    }
+
+    /// Set the number of confirmations a block needs before results about
+    /// it are cached. With 0 the depth is not checked.
+    pub fn min_confirmations(mut self, confirmations: u32) -> CachingClient<C> {
+        self.min_confirmations = confirmations;
+        self
+    }
+
+    /// The wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.rpc
+    }
+
+    pub fn into_inner(self) -> C {
+        self.rpc
+    }
+
+    /// Drop all results cached in memory.
+    pub fn clear_memory(&self) {
+        self.memory.lock().unwrap().clear();
+    }
+
+    fn disk_path(&self, key: &str, block: &Block) -> Option<PathBuf> {
+        // Results by height can change in a reorg, so they don't outlive
+        // the process.
+        if let Block::Height(_) = *block {
+            return None;
+        }
+        self.disk.as_ref().map(|dir| dir.join(sha256::Hash::hash(key.as_bytes()).to_hex()))
+    }
+
+    fn lookup(&self, key: &str, block: &Block) -> Option<serde_json::Value> {
+        if let Some(value) = self.memory.lock().unwrap().get(key) {
+            return Some(value);
+        }
+        let path = self.disk_path(key, block)?;
+        let bytes = fs::read(&path).ok()?;
+        match serde_json::from_slice(&bytes) {
+            Ok(value) => {
+                self.memory.lock().unwrap().insert(key.to_owned(), &value);
+                Some(value)
+            }
+            Err(e) => {
+                warn!("ignoring corrupt cache file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    fn store(&self, key: String, block: &Block, value: &serde_json::Value) {
+        if let Some(path) = self.disk_path(&key, block) {
+            // The cache is an optimization, failing to write it is no error.
+            if let Err(e) = fs::write(&path, value.to_string()) {
+                warn!("failed to write cache file {}: {}", path.display(), e);
+            }
+        }
+        self.memory.lock().unwrap().insert(key, value);
+    }
+
+    /// Whether the result of a call is final at the configured depth.
+    fn is_final(&self, block: &Block) -> Result<bool> {
+        if self.min_confirmations == 0 {
+            return Ok(true);
+        }
+        Ok(match *block {
+            Block::Hash(ref hash) => {
+                let header: serde_json::Value =
+                    self.rpc.call("getblockheader", slice::from_ref(hash))?;
+                self.is_deep(&header)
+            }
+            Block::Height(height) => {
+                let count: u64 = self.rpc.call("getblockcount", &[])?;
+                count as i64 - height as i64 + 1 >= i64::from(self.min_confirmations)
+            }
+        })
+    }
+
+    /// Like [CachingClient::is_final] for several blocks, with the headers
+    /// fetched in one batch.
+    fn are_final(&self, blocks: &[&Block]) -> Result<Vec<bool>> {
+        if self.min_confirmations == 0 {
+            return Ok(vec![true; blocks.len()]);
+        }
+        let hashes: Vec<Vec<serde_json::Value>> = blocks
+            .iter()
+            .filter_map(|block| match **block {
+                Block::Hash(ref hash) => Some(vec![hash.clone()]),
+                Block::Height(_) => None,
+            })
+            .collect();
+        let headers: Vec<serde_json::Value> = if hashes.is_empty() {
+            Vec::new()
+        } else {
+            self.rpc.call_batch("getblockheader", &hashes)?
+        };
+        let mut headers = headers.iter();
+        let mut count = None;
+        blocks
+            .iter()
+            .map(|block| {
+                Ok(match **block {
+                    Block::Hash(_) => {
+                        self.is_deep(headers.next().ok_or(Error::UnexpectedStructure)?)
+                    }
+                    Block::Height(height) => {
+                        if count.is_none() {
+                            count = Some(self.rpc.call::<u64>("getblockcount", &[])?);
+                        }
+                        let count = count.unwrap_or(0);
+                        count as i64 - height as i64 + 1 >= i64::from(self.min_confirmations)
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Whether the block of the verbose `header` has enough confirmations.
+    fn is_deep(&self, header: &serde_json::Value) -> bool {
+        // Stale blocks have -1 confirmations.
+        header["confirmations"].as_i64().unwrap_or(-1) >= i64::from(self.min_confirmations)
+    }
+}
+
+fn cache_key(cmd: &str, args: &[serde_json::Value]) -> String {
+    format!("{}{}", cmd, serde_json::Value::from(args.to_vec()))
+}
+
+/// The block a cacheable call is about.
+enum Block {
+    Hash(serde_json::Value),
+    Height(u64),
+}
+
+/// The block of a call whose result can be cached, if any.
+fn cacheable(cmd: &str, args: &[serde_json::Value]) -> Option<Block> {
+    let arg = |i: usize| args.get(i).cloned().unwrap_or(serde_json::Value::Null);
+    match cmd {
+        "getblock" if arg(1) == 0 => Some(Block::Hash(arg(0))),
+        "getblockheader" if arg(1) == false => Some(Block::Hash(arg(0))),
+        "getrawtransaction" if arg(1) == false && arg(2).is_string() => Some(Block::Hash(arg(2))),
+        "getblockfilter" if args.len() == 1 => Some(Block::Hash(arg(0))),
+        "getblockhash" => arg(0).as_u64().map(Block::Height),
+        _ => None,
+    }
+}
+
+impl<C: RpcApi> RpcApi for CachingClient<C> {
+    fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T> {
+        let block = match cacheable(cmd, args) {
+            Some(block) => block,
+            None => return self.rpc.call(cmd, args),
+        };
+        let key = cache_key(cmd, args);
+        if let Some(value) = self.lookup(&key, &block) {
+            return Ok(serde_json::from_value(value)?);
+        }
+
+        let value: serde_json::Value = self.rpc.call(cmd, args)?;
+        if self.is_final(&block)? {
+            self.store(key, &block, &value);
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Cached results are taken from the cache, the others are sent in one
+    /// batch, and so are the depth checks of the results to cache.
+    fn call_batch<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[Vec<serde_json::Value>],
+    ) -> Result<Vec<T>> {
+        let blocks: Vec<Option<Block>> = args.iter().map(|args| cacheable(cmd, args)).collect();
+        if blocks.iter().all(Option::is_none) {
+            return self.rpc.call_batch(cmd, args);
+        }
+
+        let keys: Vec<String> = args.iter().map(|args| cache_key(cmd, args)).collect();
+        let mut values: Vec<Option<serde_json::Value>> = keys
+            .iter()
+            .zip(&blocks)
+            .map(|(key, block)| block.as_ref().and_then(|block| self.lookup(key, block)))
+            .collect();
+        let missing: Vec<usize> = (0..args.len()).filter(|&i| values[i].is_none()).collect();
+        if !missing.is_empty() {
+            let missing_args: Vec<Vec<serde_json::Value>> =
+                missing.iter().map(|&i| args[i].clone()).collect();
+            let fetched: Vec<serde_json::Value> = self.rpc.call_batch(cmd, &missing_args)?;
+            let to_cache: Vec<usize> =
+                missing.iter().cloned().filter(|&i| blocks[i].is_some()).collect();
+            let to_check: Vec<&Block> =
+                to_cache.iter().filter_map(|&i| blocks[i].as_ref()).collect();
+            let finals = self.are_final(&to_check)?;
+            for (&i, value) in missing.iter().zip(fetched) {
+                values[i] = Some(value);
+            }
+            for (&i, &is_final) in to_cache.iter().zip(&finals) {
+                if let (Some(ref value), Some(ref block)) = (&values[i], &blocks[i]) {
+                    if is_final {
+                        self.store(keys[i].clone(), block, value);
+                    }
+                }
+            }
+        }
+        values
+            .into_iter()
+            .map(|value| Ok(serde_json::from_value(value.ok_or(Error::UnexpectedStructure)?)?))
+            .collect()
+    }
+
+    /// Cached results are decoded from the cache, the others are passed
+    /// through so they can be streamed.
+    fn call_hex<T: Decodable>(&self, cmd: &str, args: &[serde_json::Value]) -> Result<T> {
+        if cacheable(cmd, args).is_none() {
+            return self.rpc.call_hex(cmd, args);
+        }
+        let hex: String = self.call(cmd, args)?;
+        let bytes: Vec<u8> = FromHex::from_hex(&hex)?;
+        Ok(bitcoin::consensus::encode::deserialize(&bytes)?)
+    }
+}
+
+/// A least recently used cache of JSON values, bounded by their size.
+struct Lru {
+    /// The maximum size of the keys and values in bytes.
+    capacity: usize,
+    size: usize,
+    /// The values, their size and the tick they were last used at.
+    entries: HashMap<String, (serde_json::Value, usize, u64)>,
+    /// The keys by the tick they were last used at.
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Lru {
+        Lru {
+            capacity,
+            size: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<serde_json::Value> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(key)?;
+        let key = self.order.remove(&entry.2).expect("entries and order are in sync");
+        self.order.insert(self.tick, key);
+        entry.2 = self.tick;
+        Some(entry.0.clone())
+    }
+
+    fn insert(&mut self, key: String, value: &serde_json::Value) {
+        // Raw results are hex strings, their length is their size.
+        let size = key.len()
+            + match *value {
+                serde_json::Value::String(ref s) => s.len(),
+                ref value => value.to_string().len(),
+            };
+        if size > self.capacity {
+            return;
+        }
+        self.tick += 1;
+        let entry = (value.clone(), size, self.tick);
+        if let Some((_, old_size, tick)) = self.entries.insert(key.clone(), entry) {
+            self.order.remove(&tick);
+            self.size -= old_size;
+        }
+        self.order.insert(self.tick, key);
+        self.size += size;
+
+        while self.size > self.capacity {
+            let oldest = *self.order.keys().next().expect("cache is not empty");
+            let key = self.order.remove(&oldest).expect("key exists");
+            let (_, size, _) = self.entries.remove(&key).expect("entries and order are in sync");
+            self.size -= size;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.size = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::consensus::encode::serialize;
+    use bitcoin::util::hash::BitcoinHash;
+    use std::env;
+    use std::process;
+    use testutil::FakeNode;
+
+    #[test]
+    fn test_caching_client() {
+        // Room for a block and a block hash, but not one more block hash.
+        let node = FakeNode::new(10);
+        let capacity = 2 * serialize(&node.blocks[7]).len() + 200;
+        let rpc = CachingClient::new(node, capacity).min_confirmations(3);
+        let deep = rpc.inner().blocks[7].bitcoin_hash();
+        let shallow = rpc.inner().blocks[8].bitcoin_hash();
+
+        assert_eq!(rpc.get_block(&deep).unwrap(), rpc.inner().blocks[7]);
+        assert_eq!(rpc.inner().take_calls(), vec!["getblock", "getblockheader"]);
+        assert_eq!(rpc.get_block(&deep).unwrap(), rpc.inner().blocks[7]);
+        assert!(rpc.inner().take_calls().is_empty());
+
+        // Not deep enough to be cached.
+        rpc.get_block(&shallow).unwrap();
+        rpc.get_block(&shallow).unwrap();
+        assert_eq!(rpc.inner().take_calls().len(), 4);
+
+        assert_eq!(rpc.get_block_hash(7).unwrap(), deep);
+        assert_eq!(rpc.get_block_hash(8).unwrap(), shallow);
+        assert_eq!(rpc.get_block_hash(7).unwrap(), deep);
+        assert_eq!(
+            rpc.inner().take_calls(),
+            vec!["getblockhash", "getblockcount", "getblockhash", "getblockcount"]
+        );
+
+        // The least recently used block was evicted.
+        rpc.get_block_hash(0).unwrap();
+        rpc.get_block(&deep).unwrap();
+        assert_eq!(
+            rpc.inner().take_calls(),
+            vec!["getblockhash", "getblockcount", "getblock", "getblockheader"]
+        );
+
+        // Mutable results are not cached.
+        rpc.call::<serde_json::Value>("getblockchaininfo", &[]).unwrap();
+        rpc.call::<serde_json::Value>("getblockchaininfo", &[]).unwrap();
+        assert_eq!(rpc.inner().take_calls().len(), 2);
+    }
+
+    #[test]
+    fn test_caching_client_disk() {
+        let dir = env::temp_dir().join(format!("bitcoincore-rpc-cache-{}", process::id()));
+        let rpc = CachingClient::new(FakeNode::new(10), 0).disk_cache(dir.clone()).unwrap();
+        let hash = rpc.inner().blocks[2].bitcoin_hash();
+        rpc.get_block(&hash).unwrap();
+        rpc.get_block_hash(3).unwrap();
+        assert_eq!(rpc.inner().take_calls().len(), 4);
+
+        let rpc = CachingClient::new(rpc.into_inner(), 1 << 20).disk_cache(dir.clone()).unwrap();
+        assert_eq!(rpc.get_block(&hash).unwrap(), rpc.inner().blocks[2]);
+        rpc.clear_memory();
+        assert_eq!(rpc.get_block(&hash).unwrap(), rpc.inner().blocks[2]);
+        assert!(rpc.inner().take_calls().is_empty());
+        // Block hashes by height are not kept across runs.
+        assert_eq!(rpc.get_block_hash(3).unwrap(), rpc.inner().hash(3));
+        assert_eq!(rpc.inner().take_calls(), vec!["getblockhash", "getblockcount"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_caching_client_batch() {
+        let rpc = CachingClient::new(FakeNode::new(10), 1 << 20).min_confirmations(3);
+        let hashes: Vec<_> = (5..9).map(|h| rpc.inner().hash(h)).collect();
+
+        let blocks: Vec<bitcoin::Block> = rpc.get_by_ids(&hashes).unwrap();
+        assert_eq!(blocks, rpc.inner().blocks[5..9].to_vec());
+        assert_eq!(rpc.inner().take_calls(), vec!["batch getblock x4", "batch getblockheader x4"]);
+
+        // Only the block that was too shallow is fetched again.
+        let blocks: Vec<bitcoin::Block> = rpc.get_by_ids(&hashes).unwrap();
+        assert_eq!(blocks, rpc.inner().blocks[5..9].to_vec());
+        assert_eq!(rpc.inner().take_calls(), vec!["batch getblock x1", "batch getblockheader x1"]);
+
+        let heights: Vec<Vec<serde_json::Value>> = vec![vec![3.into()], vec![8.into()]];
+        let fetched: Vec<bitcoin::BlockHash> = rpc.call_batch("getblockhash", &heights).unwrap();
+        assert_eq!(fetched, vec![rpc.inner().hash(3), rpc.inner().hash(8)]);
+        assert_eq!(rpc.inner().take_calls(), vec!["batch getblockhash x2", "getblockcount"]);
+
+        // Calls that can't be cached are passed on as a batch.
+        let infos: Vec<serde_json::Value> =
+            rpc.call_batch("getblockchaininfo", &[vec![], vec![]]).unwrap();
+        assert_eq!(infos.len(), 2);
+        assert_eq!(rpc.inner().take_calls(), vec!["batch getblockchaininfo x2"]);
+    }
+}
@@ -12,6 +12,7 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::iter::FromIterator;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::{fmt, result};
 
 use bitcoin;
@@ -19,6 +20,7 @@ use jsonrpc;
 use serde;
 use serde_json;
 
+use bitcoin::consensus::encode::Decodable;
 use bitcoin::hashes::hex::{FromHex, ToHex};
 use bitcoin::secp256k1::{self, SecretKey, Signature};
 use bitcoin::{Address, Amount, Block, BlockHeader, OutPoint, PrivateKey, PublicKey, Transaction};
@@ -28,7 +30,12 @@ use serde::{Deserialize, Serialize};
 
 use error::*;
 use json;
+use message::MessageSignature;
+use metrics::{self, Metrics};
+use limit::Limiter;
+use redact::RedactionTable;
 use queryable;
+use stream::HttpStream;
 
 /// Crate-specific Result type, shorthand for `std::result::Result` with our
 /// crate-specific Error type;
@@ -140,6 +147,69 @@ fn handle_defaults<'a, 'b>(
     }
 }
 
+/// Strip the trailing nulls from the argument list.
+///
+/// Unlike [handle_defaults], this keeps the nulls in between. Only use it for
+/// commands where Bitcoin Core treats a null argument as if it was omitted.
+fn trim_trailing_nulls(args: &[serde_json::Value]) -> &[serde_json::Value] {
+    let len = args.iter().rposition(|a| *a != serde_json::Value::Null).map_or(0, |i| i + 1);
+    &args[..len]
+}
+
+/// Build the argument list for "sendtoaddress".
+fn send_to_address_args(
+    address: &Address,
+    amount: Amount,
+    options: &json::SendToAddressOptions,
+    verbose: Option<bool>,
+) -> Result<Vec<serde_json::Value>> {
+    Ok(vec![
+        address.to_string().into(),
+        into_json(amount.as_btc())?,
+        opt_into_json(options.comment.as_ref())?,
+        opt_into_json(options.comment_to.as_ref())?,
+        opt_into_json(options.subtract_fee_from_amount)?,
+        opt_into_json(options.replaceable)?,
+        opt_into_json(options.conf_target)?,
+        opt_into_json(options.estimate_mode)?,
+        opt_into_json(options.avoid_reuse)?,
+        opt_into_json(options.fee_rate)?,
+        opt_into_json(verbose)?,
+    ])
+}
+
+/// Build the argument list for "sendmany".
+fn send_many_args(
+    amounts: &HashMap<Address, Amount>,
+    options: Option<&json::SendManyOptions>,
+    verbose: Option<bool>,
+) -> Result<Vec<serde_json::Value>> {
+    let amounts = serde_json::Map::from_iter(
+        amounts.iter().map(|(k, v)| (k.to_string(), serde_json::Value::from(v.as_btc()))),
+    );
+    let default = json::SendManyOptions::default();
+    let options = options.unwrap_or(&default);
+    let subtract_fee_from = if options.subtract_fee_from.is_empty() {
+        null()
+    } else {
+        into_json(&options.subtract_fee_from)?
+    };
+    Ok(vec![
+        // The "fromaccount" argument is a dummy and must be set to "".
+        "".into(),
+        amounts.into(),
+        // The "minconf" argument is ignored.
+        null(),
+        opt_into_json(options.comment.as_ref())?,
+        subtract_fee_from,
+        opt_into_json(options.replaceable)?,
+        opt_into_json(options.conf_target)?,
+        opt_into_json(options.estimate_mode)?,
+        opt_into_json(options.fee_rate)?,
+        opt_into_json(verbose)?,
+    ])
+}
+
 /// Convert a possible-null result into an Option.
 fn opt_result<T: for<'a> serde::de::Deserialize<'a>>(
     result: serde_json::Value,
@@ -223,6 +293,28 @@ pub trait RpcApi: Sized {
         args: &[serde_json::Value],
     ) -> Result<T>;
 
+    /// Call a `cmd` rpc that returns a hex encoded object and decode it.
+    ///
+    /// Implementations can override this to decode the object while the
+    /// response is received instead of buffering it several times.
+    fn call_hex<T: Decodable>(&self, cmd: &str, args: &[serde_json::Value]) -> Result<T> {
+        let hex: String = self.call(cmd, args)?;
+        let bytes: Vec<u8> = FromHex::from_hex(&hex)?;
+        Ok(bitcoin::consensus::encode::deserialize(&bytes)?)
+    }
+
+    /// Call a `cmd` rpc once for every list of arguments in `args`.
+    ///
+    /// Implementations can override this to send all calls in a single
+    /// JSON-RPC batch.
+    fn call_batch<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[Vec<serde_json::Value>],
+    ) -> Result<Vec<T>> {
+        args.iter().map(|args| self.call(cmd, args)).collect()
+    }
+
     /// Query an object implementing `Querable` type
     fn get_by_id<T: queryable::Queryable<Self>>(
         &self,
@@ -231,6 +323,14 @@ pub trait RpcApi: Sized {
         T::query(&self, &id)
     }
 
+    /// Query several objects implementing `Queryable`, in the order of `ids`.
+    fn get_by_ids<T: queryable::Queryable<Self>>(
+        &self,
+        ids: &[<T as queryable::Queryable<Self>>::Id],
+    ) -> Result<Vec<T>> {
+        T::query_many(self, ids)
+    }
+
     fn add_multisig_address(
         &self,
         nrequired: usize,
@@ -281,10 +381,80 @@ pub trait RpcApi: Sized {
         Ok(secp256k1::SecretKey::from_slice(&bytes)?)
     }
 
+    /// Dump all wallet keys in a human-readable format to a file on the
+    /// node, see [parse_wallet_dump] to read it.
+    fn dump_wallet(&self, filename: &str) -> Result<json::DumpWalletResult> {
+        self.call("dumpwallet", &[into_json(filename)?])
+    }
+
+    /// Import keys from a wallet dump file on the node, as created by
+    /// [dump_wallet].
+    fn import_wallet(&self, filename: &str) -> Result<()> {
+        self.call("importwallet", &[into_json(filename)?])
+    }
+
     fn encrypt_wallet(&self, passphrase: &str) -> Result<()> {
         self.call("encryptwallet", &[into_json(passphrase)?])
     }
 
+    /// Store the wallet decryption key in memory for `timeout` seconds.
+    ///
+    /// See [UnlockedWallet] to keep the wallet unlocked only for as long as
+    /// needed.
+    fn wallet_passphrase(&self, passphrase: &str, timeout: u64) -> Result<()> {
+        self.call("walletpassphrase", &[into_json(passphrase)?, into_json(timeout)?])
+    }
+
+    /// Remove the wallet encryption key from memory, locking the wallet.
+    fn wallet_lock(&self) -> Result<()> {
+        self.call("walletlock", &[])
+    }
+
+    /// Change the wallet passphrase from `old_passphrase` to `new_passphrase`.
+    fn wallet_passphrase_change(&self, old_passphrase: &str, new_passphrase: &str) -> Result<()> {
+        self.call(
+            "walletpassphrasechange",
+            &[into_json(old_passphrase)?, into_json(new_passphrase)?],
+        )
+    }
+
+    /// Set or generate a new HD wallet seed.
+    ///
+    /// # Arguments
+    ///
+    /// 1. `new_keypool`: Whether to flush the old unused addresses from the
+    ///    keypool and regenerate it (default true).
+    /// 2. `seed`: The key to use as the new seed; a random seed is generated
+    ///    if omitted.
+    fn set_hd_seed(&self, new_keypool: Option<bool>, seed: Option<&PrivateKey>) -> Result<()> {
+        let mut args = [opt_into_json(new_keypool)?, opt_into_json(seed)?];
+        self.call("sethdseed", handle_defaults(&mut args, &[true.into(), null()]))
+    }
+
+    /// Upgrade the wallet to `version`, or to the latest version if omitted
+    /// (Bitcoin Core v0.21+).
+    fn upgrade_wallet(&self, version: Option<u32>) -> Result<json::UpgradeWalletResult> {
+        let mut args = [opt_into_json(version)?];
+        self.call("upgradewallet", handle_defaults(&mut args, &[null()]))
+    }
+
+    /// Rescan the local blockchain for wallet related transactions.
+    fn rescan_blockchain(
+        &self,
+        start_height: Option<u64>,
+        stop_height: Option<u64>,
+    ) -> Result<json::RescanBlockchainResult> {
+        let mut args = [opt_into_json(start_height)?, opt_into_json(stop_height)?];
+        self.call("rescanblockchain", handle_defaults(&mut args, &[0.into(), null()]))
+    }
+
+    /// Stop the current wallet rescan.
+    ///
+    /// Returns `false` if there was no rescan to abort.
+    fn abort_rescan(&self) -> Result<bool> {
+        self.call("abortrescan", &[])
+    }
+
     //TODO(stevenroose) verify if return type works
     fn get_difficulty(&self) -> Result<BigUint> {
         self.call("getdifficulty", &[])
@@ -295,9 +465,7 @@ pub trait RpcApi: Sized {
     }
 
     fn get_block(&self, hash: &bitcoin::BlockHash) -> Result<Block> {
-        let hex: String = self.call("getblock", &[into_json(hash)?, 0.into()])?;
-        let bytes: Vec<u8> = FromHex::from_hex(&hex)?;
-        Ok(bitcoin::consensus::encode::deserialize(&bytes)?)
+        self.call_hex("getblock", &[into_json(hash)?, 0.into()])
     }
 
     fn get_block_hex(&self, hash: &bitcoin::BlockHash) -> Result<String> {
@@ -307,7 +475,25 @@ pub trait RpcApi: Sized {
     fn get_block_info(&self, hash: &bitcoin::BlockHash) -> Result<json::GetBlockResult> {
         self.call("getblock", &[into_json(hash)?, 1.into()])
     }
-    //TODO(stevenroose) add getblock_txs
+
+    /// Get a block with all its transactions decoded.
+    fn get_block_verbose_tx(
+        &self,
+        hash: &bitcoin::BlockHash,
+    ) -> Result<json::GetBlockVerboseTxResult> {
+        self.call("getblock", &[into_json(hash)?, 2.into()])
+    }
+
+    /// Get a block with all its transactions decoded, including the outputs
+    /// spent by their inputs.
+    ///
+    /// Requires Bitcoin Core v23 or later.
+    fn get_block_verbose_tx_with_prevouts(
+        &self,
+        hash: &bitcoin::BlockHash,
+    ) -> Result<json::GetBlockVerboseTxResult> {
+        self.call("getblock", &[into_json(hash)?, 3.into()])
+    }
 
     fn get_block_header_raw(&self, hash: &bitcoin::BlockHash) -> Result<BlockHeader> {
         let hex: String = self.call("getblockheader", &[into_json(hash)?, false.into()])?;
@@ -319,6 +505,12 @@ pub trait RpcApi: Sized {
         self.call("getblockheader", &[into_json(hash)?, true.into()])
     }
 
+    /// Get the header of the block at the given height in the active chain.
+    fn get_block_header_by_height(&self, height: u64) -> Result<BlockHeader> {
+        let hash = self.get_block_hash(height)?;
+        self.get_block_header_raw(&hash)
+    }
+
     fn get_mining_info(&self) -> Result<json::GetMiningInfoResult> {
         self.call("getmininginfo", &[])
     }
@@ -373,6 +565,35 @@ pub trait RpcApi: Sized {
         self.call("getrawtransaction", handle_defaults(&mut args, &[null()]))
     }
 
+    /// Compute per block statistics for the given block.
+    ///
+    /// If `stats` is given, only the selected statistics are computed and
+    /// all other fields of the result are `None`.
+    fn get_block_stats(
+        &self,
+        hash_or_height: json::HashOrHeight,
+        stats: Option<&[json::BlockStatsFields]>,
+    ) -> Result<json::GetBlockStatsResult> {
+        let mut args = [into_json(hash_or_height)?, opt_into_json(stats)?];
+        self.call("getblockstats", handle_defaults(&mut args, &[null()]))
+    }
+
+    /// Compute statistics about the total number and rate of transactions
+    /// in the chain.
+    ///
+    /// The window spans `nblocks` blocks (one month by default) and ends at
+    /// `block_hash` (the chain tip by default).
+    fn get_chain_tx_stats(
+        &self,
+        nblocks: Option<u32>,
+        block_hash: Option<&bitcoin::BlockHash>,
+    ) -> Result<json::GetChainTxStatsResult> {
+        // Bitcoin Core only fits the window to the block when `nblocks` is
+        // null, so a missing `nblocks` is sent as null rather than a default.
+        let args = [opt_into_json(nblocks)?, opt_into_json(block_hash)?];
+        self.call("getchaintxstats", trim_trailing_nulls(&args))
+    }
+
     fn get_block_filter(&self, block_hash: &bitcoin::BlockHash) -> Result<json::GetBlockFilterResult> {
         self.call("getblockfilter", &[into_json(block_hash)?])
     }
@@ -395,6 +616,14 @@ pub trait RpcApi: Sized {
         )?)
     }
 
+    /// Get the total amount received by the addresses with `label`.
+    fn get_received_by_label(&self, label: &str, minconf: Option<u32>) -> Result<Amount> {
+        let mut args = [label.into(), opt_into_json(minconf)?];
+        Ok(Amount::from_btc(
+            self.call("getreceivedbylabel", handle_defaults(&mut args, &[null()]))?,
+        )?)
+    }
+
     fn get_transaction(
         &self,
         txid: &bitcoin::Txid,
@@ -420,6 +649,28 @@ pub trait RpcApi: Sized {
         self.call("listtransactions", handle_defaults(&mut args, &[10.into(), 0.into(), null()]))
     }
 
+    /// Get all wallet transactions in blocks since `blockhash`, or all
+    /// transactions if omitted.
+    ///
+    /// If `include_removed` is set (the default), transactions that were
+    /// removed by a reorg are listed in the `removed` field of the result.
+    fn list_since_block(
+        &self,
+        blockhash: Option<&bitcoin::BlockHash>,
+        target_confirmations: Option<usize>,
+        include_watchonly: Option<bool>,
+        include_removed: Option<bool>,
+    ) -> Result<json::ListSinceBlockResult> {
+        let mut args = [
+            opt_into_json(blockhash)?,
+            opt_into_json(target_confirmations)?,
+            opt_into_json(include_watchonly)?,
+            opt_into_json(include_removed)?,
+        ];
+        let defaults = [into_json("")?, 1.into(), false.into(), null()];
+        self.call("listsinceblock", handle_defaults(&mut args, &defaults))
+    }
+
     fn get_tx_out(
         &self,
         txid: &bitcoin::Txid,
@@ -430,6 +681,35 @@ pub trait RpcApi: Sized {
         opt_result(self.call("gettxout", handle_defaults(&mut args, &[null()]))?)
     }
 
+    /// Returns statistics about the unspent transaction output set.
+    ///
+    /// Note that this call may take some time.
+    fn get_tx_out_set_info(&self) -> Result<json::GetTxOutSetInfoResult> {
+        self.call("gettxoutsetinfo", &[])
+    }
+
+    /// Scan the unspent transaction output set for entries that match the
+    /// given descriptors or addresses.
+    ///
+    /// This blocks until the scan is finished. Only one scan can be active at
+    /// a time; it can be monitored with [scan_tx_out_set_status] and aborted
+    /// with [scan_tx_out_set_abort] from another thread.
+    fn scan_tx_out_set(&self, requests: &[json::ScanTxOutRequest]) -> Result<json::ScanTxOutResult> {
+        self.call("scantxoutset", &["start".into(), into_json(requests)?])
+    }
+
+    /// Abort the currently running scan.
+    ///
+    /// Returns `false` if there was no scan to abort.
+    fn scan_tx_out_set_abort(&self) -> Result<bool> {
+        self.call("scantxoutset", &["abort".into()])
+    }
+
+    /// Get the progress of the currently running scan, if any.
+    fn scan_tx_out_set_status(&self) -> Result<Option<json::ScanTxOutStatusResult>> {
+        opt_result(self.call("scantxoutset", &["status".into()])?)
+    }
+
     fn get_tx_out_proof(
         &self,
         txids: &[bitcoin::Txid],
@@ -440,6 +720,15 @@ pub trait RpcApi: Sized {
         Ok(FromHex::from_hex(&hex)?)
     }
 
+    /// Verify a proof returned by [get_tx_out_proof] and return the txids
+    /// it commits to.
+    ///
+    /// This trusts the node, see [verify_tx_out_proof_locally] to check the
+    /// proof without doing so.
+    fn verify_tx_out_proof(&self, proof: &[u8]) -> Result<Vec<bitcoin::Txid>> {
+        self.call("verifytxoutproof", &[proof.to_hex().into()])
+    }
+
     fn import_public_key(
         &self,
         pubkey: &PublicKey,
@@ -492,10 +781,49 @@ pub trait RpcApi: Sized {
         self.call("importmulti", handle_defaults(&mut args, &[null()]))
     }
 
+    /// Import funds into a pruned wallet without a rescan.
+    ///
+    /// `tx_out_proof` is the proof that the transaction is in a block, as
+    /// returned by [get_tx_out_proof].
+    fn import_pruned_funds<R: RawTx>(&self, raw_tx: R, tx_out_proof: &[u8]) -> Result<()> {
+        self.call("importprunedfunds", &[raw_tx.raw_hex().into(), tx_out_proof.to_hex().into()])
+    }
+
+    /// Delete a transaction that was imported with [import_pruned_funds]
+    /// from the wallet.
+    fn remove_pruned_funds(&self, txid: &bitcoin::Txid) -> Result<()> {
+        self.call("removeprunedfunds", &[into_json(txid)?])
+    }
+
     fn set_label(&self, address: &Address, label: &str) -> Result<()> {
         self.call("setlabel", &[address.to_string().into(), label.into()])
     }
 
+    /// Get the addresses assigned to `label` and their purpose.
+    fn get_addresses_by_label(
+        &self,
+        label: &str,
+    ) -> Result<HashMap<Address, json::GetAddressInfoResultLabelPurpose>> {
+        let result: HashMap<Address, json::GetAddressesByLabelResultEntry> =
+            self.call("getaddressesbylabel", &[label.into()])?;
+        Ok(result.into_iter().map(|(address, entry)| (address, entry.purpose)).collect())
+    }
+
+    /// List all labels, or only those used for the given purpose.
+    fn list_labels(
+        &self,
+        purpose: Option<json::GetAddressInfoResultLabelPurpose>,
+    ) -> Result<Vec<String>> {
+        let mut args = [opt_into_json(purpose)?];
+        self.call("listlabels", handle_defaults(&mut args, &[null()]))
+    }
+
+    /// List groups of addresses whose common ownership has been revealed by
+    /// using them together as inputs or as change.
+    fn list_address_groupings(&self) -> Result<json::ListAddressGroupingsResult> {
+        self.call("listaddressgroupings", &[])
+    }
+
     fn key_pool_refill(&self, new_size: Option<usize>) -> Result<()> {
         let mut args = [opt_into_json(new_size)?];
         self.call("keypoolrefill", handle_defaults(&mut args, &[null()]))
@@ -554,6 +882,21 @@ pub trait RpcApi: Sized {
         self.call("listreceivedbyaddress", handle_defaults(&mut args, &defaults))
     }
 
+    fn list_received_by_label(
+        &self,
+        minconf: Option<u32>,
+        include_empty: Option<bool>,
+        include_watchonly: Option<bool>,
+    ) -> Result<Vec<json::ListReceivedByLabelResult>> {
+        let mut args = [
+            opt_into_json(minconf)?,
+            opt_into_json(include_empty)?,
+            opt_into_json(include_watchonly)?,
+        ];
+        let defaults = [1.into(), false.into(), null()];
+        self.call("listreceivedbylabel", handle_defaults(&mut args, &defaults))
+    }
+
     fn create_raw_transaction_hex(
         &self,
         utxos: &[json::CreateRawTransactionInput],
@@ -643,6 +986,30 @@ pub trait RpcApi: Sized {
         self.call("signrawtransactionwithkey", handle_defaults(&mut args, &defaults))
     }
 
+    fn decode_raw_transaction<R: RawTx>(
+        &self,
+        tx: R,
+        is_witness: Option<bool>,
+    ) -> Result<json::DecodeRawTransactionResult> {
+        let mut args = [tx.raw_hex().into(), opt_into_json(is_witness)?];
+        self.call("decoderawtransaction", handle_defaults(&mut args, &[null()]))
+    }
+
+    /// Decode a hex-encoded script.
+    fn decode_script<R: RawTx>(&self, script: R) -> Result<json::DecodeScriptResult> {
+        self.call("decodescript", &[script.raw_hex().into()])
+    }
+
+    /// Combine multiple partially signed versions of the same transaction
+    /// into one.
+    fn combine_raw_transaction<R: RawTx>(&self, txs: &[R]) -> Result<Transaction> {
+        let hexes: Vec<serde_json::Value> =
+            txs.iter().cloned().map(|r| r.raw_hex().into()).collect();
+        let hex: String = self.call("combinerawtransaction", &[hexes.into()])?;
+        let bytes: Vec<u8> = FromHex::from_hex(&hex)?;
+        Ok(bitcoin::consensus::encode::deserialize(&bytes)?)
+    }
+
     fn test_mempool_accept<R: RawTx>(&self, rawtxs: &[R]) -> Result<Vec<json::TestMempoolAccept>> {
         let hexes: Vec<serde_json::Value> =
             rawtxs.to_vec().into_iter().map(|r| r.raw_hex().into()).collect();
@@ -663,6 +1030,24 @@ pub trait RpcApi: Sized {
         self.call("verifymessage", &args)
     }
 
+    /// Sign a message with the key of an address in the wallet.
+    fn sign_message(&self, address: &Address, message: &str) -> Result<MessageSignature> {
+        let args = [address.to_string().into(), into_json(message)?];
+        let signature: String = self.call("signmessage", &args)?;
+        MessageSignature::from_base64(&signature)
+    }
+
+    /// Sign a message with a private key, the wallet is not used.
+    fn sign_message_with_privkey(
+        &self,
+        privkey: &PrivateKey,
+        message: &str,
+    ) -> Result<MessageSignature> {
+        let args = [privkey.to_string().into(), into_json(message)?];
+        let signature: String = self.call("signmessagewithprivkey", &args)?;
+        MessageSignature::from_base64(&signature)
+    }
+
     /// Generate new address under own control
     fn get_new_address(
         &self,
@@ -672,10 +1057,23 @@ pub trait RpcApi: Sized {
         self.call("getnewaddress", &[opt_into_json(label)?, opt_into_json(address_type)?])
     }
 
+    /// Generate a new address for receiving change.
+    fn get_raw_change_address(&self, address_type: Option<json::AddressType>) -> Result<Address> {
+        let mut args = [opt_into_json(address_type)?];
+        self.call("getrawchangeaddress", handle_defaults(&mut args, &[null()]))
+    }
+
     fn get_address_info(&self, address: &Address) -> Result<json::GetAddressInfoResult> {
         self.call("getaddressinfo", &[address.to_string().into()])
     }
 
+    /// Derive the addresses of a descriptor, `range` is required for
+    /// ranged descriptors.
+    fn derive_addresses(&self, descriptor: &str, range: Option<[u32; 2]>) -> Result<Vec<Address>> {
+        let mut args = [into_json(descriptor)?, opt_into_json(range)?];
+        self.call("deriveaddresses", handle_defaults(&mut args, &[null()]))
+    }
+
     /// Mine `block_num` blocks and pay coinbase to `address`
     ///
     /// Returns hashes of the generated blocks
@@ -683,6 +1081,46 @@ pub trait RpcApi: Sized {
         self.call("generatetoaddress", &[block_num.into(), address.to_string().into()])
     }
 
+    /// Get a block template to build a block on top of the current tip.
+    fn get_block_template(
+        &self,
+        mode: json::GetBlockTemplateModes,
+        rules: &[json::GetBlockTemplateRules],
+        capabilities: &[json::GetBlockTemplateCapabilities],
+    ) -> Result<json::GetBlockTemplateResult> {
+        #[derive(Serialize)]
+        struct Argument<'a> {
+            mode: json::GetBlockTemplateModes,
+            rules: &'a [json::GetBlockTemplateRules],
+            capabilities: &'a [json::GetBlockTemplateCapabilities],
+        }
+
+        self.call(
+            "getblocktemplate",
+            &[into_json(Argument {
+                mode,
+                rules,
+                capabilities,
+            })?],
+        )
+    }
+
+    /// Submit a new block to the network.
+    ///
+    /// Returns [Error::ReturnedError] with the reason if the block was rejected.
+    fn submit_block(&self, block: &Block) -> Result<()> {
+        let hex = bitcoin::consensus::encode::serialize(block).to_hex();
+        self.submit_block_hex(&hex)
+    }
+
+    /// Submit a new block in hex format to the network.
+    fn submit_block_hex(&self, block_hex: &str) -> Result<()> {
+        match self.call::<Option<String>>("submitblock", &[into_json(block_hex)?])? {
+            None => Ok(()),
+            Some(reason) => Err(Error::ReturnedError(reason)),
+        }
+    }
+
     /// Mine up to block_num blocks immediately (before the RPC call returns)
     /// to an address in the wallet.
     fn generate(&self, block_num: u64, maxtries: Option<u64>) -> Result<Vec<bitcoin::BlockHash>> {
@@ -699,6 +1137,36 @@ pub trait RpcApi: Sized {
         self.call("reconsiderblock", &[into_json(block_hash)?])
     }
 
+    /// Treat a block as if it were received before others with the same work.
+    fn precious_block(&self, block_hash: &bitcoin::BlockHash) -> Result<()> {
+        self.call("preciousblock", &[into_json(block_hash)?])
+    }
+
+    /// Return information about all known tips in the block tree, including
+    /// the main chain as well as orphaned branches.
+    fn get_chain_tips(&self) -> Result<json::GetChainTipsResult> {
+        self.call("getchaintips", &[])
+    }
+
+    /// Prune the blockchain up to the given height or unix timestamp.
+    ///
+    /// Returns the height of the last block pruned.
+    fn prune_blockchain(&self, height: u64) -> Result<u64> {
+        self.call("pruneblockchain", &[height.into()])
+    }
+
+    /// Verify the blockchain database.
+    ///
+    /// # Arguments
+    ///
+    /// 1. `check_level`: How thorough the block verification is (0-4,
+    ///    default 3).
+    /// 2. `nblocks`: The number of blocks to check (default 6, 0 for all).
+    fn verify_chain(&self, check_level: Option<u32>, nblocks: Option<u32>) -> Result<bool> {
+        let mut args = [opt_into_json(check_level)?, opt_into_json(nblocks)?];
+        self.call("verifychain", handle_defaults(&mut args, &[3.into(), 6.into()]))
+    }
+
     /// Get txids of all transactions in a memory pool
     fn get_raw_mempool(&self) -> Result<Vec<bitcoin::Txid>> {
         self.call("getrawmempool", &[])
@@ -728,6 +1196,65 @@ pub trait RpcApi: Sized {
         self.call("sendtoaddress", handle_defaults(&mut args, &vec![null(); 6]))
     }
 
+    /// Send an amount to the given address, with all options of
+    /// "sendtoaddress" available.
+    fn send_to_address_with_options(
+        &self,
+        address: &Address,
+        amount: Amount,
+        options: &json::SendToAddressOptions,
+    ) -> Result<bitcoin::Txid> {
+        let args = send_to_address_args(address, amount, options, None)?;
+        self.call("sendtoaddress", trim_trailing_nulls(&args))
+    }
+
+    /// Like [send_to_address_with_options], but also returns the reason for
+    /// the chosen fee (Bitcoin Core v0.21+).
+    fn send_to_address_verbose(
+        &self,
+        address: &Address,
+        amount: Amount,
+        options: &json::SendToAddressOptions,
+    ) -> Result<json::SendVerboseResult> {
+        let args = send_to_address_args(address, amount, options, Some(true))?;
+        self.call("sendtoaddress", &args)
+    }
+
+    /// Send multiple amounts to multiple addresses in a single transaction.
+    fn send_many(
+        &self,
+        amounts: &HashMap<Address, Amount>,
+        options: Option<&json::SendManyOptions>,
+    ) -> Result<bitcoin::Txid> {
+        let args = send_many_args(amounts, options, None)?;
+        self.call("sendmany", trim_trailing_nulls(&args))
+    }
+
+    /// Like [send_many], but also returns the reason for the chosen fee
+    /// (Bitcoin Core v0.21+).
+    fn send_many_verbose(
+        &self,
+        amounts: &HashMap<Address, Amount>,
+        options: Option<&json::SendManyOptions>,
+    ) -> Result<json::SendVerboseResult> {
+        let args = send_many_args(amounts, options, Some(true))?;
+        self.call("sendmany", &args)
+    }
+
+    /// Send a transaction to the given outputs (Bitcoin Core v0.21+).
+    ///
+    /// Depending on the options and on whether the wallet can sign the
+    /// transaction, the result holds either the txid of the broadcast
+    /// transaction or a PSBT.
+    fn send(
+        &self,
+        outputs: &[json::SendOutput],
+        options: Option<&json::SendOptions>,
+    ) -> Result<json::SendResult> {
+        let args = [into_json(outputs)?, null(), null(), null(), opt_into_json(options)?];
+        self.call("send", trim_trailing_nulls(&args))
+    }
+
     /// Returns data about each connected network node as an array of
     /// [`PeerInfo`][]
     ///
@@ -786,9 +1313,18 @@ pub trait RpcApi: Sized {
     }
 }
 
+/// The number of calls [Client::call_batch] sends in one batch by default.
+const DEFAULT_MAX_BATCH_SIZE: usize = 50;
+
 /// Client implements a JSON-RPC client for the Bitcoin Core daemon or compatible APIs.
 pub struct Client {
     client: jsonrpc::client::Client,
+    /// Only available if the client was created with [Client::new].
+    stream: Option<HttpStream>,
+    metrics: Option<Arc<dyn Metrics>>,
+    redactions: RedactionTable,
+    limiter: Limiter,
+    max_batch_size: usize,
 }
 
 impl fmt::Debug for Client {
@@ -808,21 +1344,117 @@ impl Client {
     pub fn new(url: String, auth: Auth) -> Result<Self> {
         let (user, pass) = auth.get_user_pass()?;
         Ok(Client {
-            client: jsonrpc::client::Client::new(url, user, pass),
+            client: jsonrpc::client::Client::new(url.clone(), user.clone(), pass.clone()),
+            stream: Some(HttpStream::new(url, user, pass)),
+            metrics: None,
+            redactions: RedactionTable::default(),
+            limiter: Limiter::new(),
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
         })
     }
 
     /// Create a new Client.
+    ///
+    /// Such a client can't stream responses, see [RpcApi::call_hex].
     pub fn from_jsonrpc(client: jsonrpc::client::Client) -> Client {
         Client {
             client: client,
+            stream: None,
+            metrics: None,
+            redactions: RedactionTable::default(),
+            limiter: Limiter::new(),
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
         }
     }
 
+    /// Report every call to `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics>) -> Client {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Get the underlying JSONRPC client.
     pub fn get_jsonrpc_client(&self) -> &jsonrpc::client::Client {
         &self.client
     }
+
+    /// Mask secrets in debug logs according to `redactions` instead of
+    /// [RedactionTable::default].
+    pub fn with_redactions(mut self, redactions: RedactionTable) -> Client {
+        self.redactions = redactions;
+        self
+    }
+
+    /// Allow `calls_per_second` calls per second on average, with bursts of
+    /// up to `burst` calls. Calls over the limit wait until they are allowed.
+    ///
+    /// Every batch sent counts as one call, like it does for the node's work
+    /// queue, see [Client::with_max_batch_size].
+    pub fn with_rate_limit(mut self, calls_per_second: f64, burst: u32) -> Client {
+        self.limiter.set_rate(calls_per_second, burst);
+        self
+    }
+
+    /// Allow at most `calls` calls at the same time, further calls wait for
+    /// one of them to finish. Keep this below the node's `-rpcworkqueue`.
+    pub fn with_max_in_flight(mut self, calls: usize) -> Client {
+        self.limiter.set_max_in_flight(calls);
+        self
+    }
+
+    /// Send at most `calls` calls in one batch, larger batches are split.
+    /// The default of 50 keeps batches of full blocks at a reasonable size.
+    pub fn with_max_batch_size(mut self, calls: usize) -> Client {
+        self.max_batch_size = calls.max(1);
+        self
+    }
+
+    fn metrics(&self) -> Option<&dyn Metrics> {
+        self.metrics.as_deref()
+    }
+
+    /// Send all calls in a single JSON-RPC batch.
+    fn send_batch<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[Vec<serde_json::Value>],
+    ) -> Result<Vec<T>> {
+        let reqs: Vec<jsonrpc::Request> =
+            args.iter().map(|args| self.client.build_request(cmd, args)).collect();
+        if log_enabled!(Debug) {
+            let reqs: Vec<String> = reqs.iter().map(|r| self.redactions.request_json(r)).collect();
+            debug!("JSON-RPC batch request: [{}]", reqs.join(","));
+        }
+
+        let (_permit, queue_wait) = self.limiter.acquire();
+        metrics::instrument(self.metrics(), cmd, queue_wait, &reqs, |measure| {
+            let sent = match self.stream {
+                Some(ref stream) if measure => {
+                    stream.send_batch(&reqs).map(|(resps, size)| (resps, Some(size)))
+                }
+                _ => self.client.send_batch(&reqs).map(|resps| (resps, None)).map_err(Error::from),
+            };
+            let (resps, size) = match sent {
+                Ok(sent) => sent,
+                Err(e) => return (Err(e), None),
+            };
+            if log_enabled!(Debug) {
+                let resps: Vec<String> = resps
+                    .iter()
+                    .map(|r| match *r {
+                        Some(ref r) => self.redactions.response_json(cmd, r),
+                        None => "null".to_owned(),
+                    })
+                    .collect();
+                debug!("JSON-RPC batch response: [{}]", resps.join(","));
+            }
+            let results = resps
+                .into_iter()
+                .map(|resp| Ok(resp.ok_or(Error::UnexpectedStructure)?.into_result()?))
+                .collect();
+            (results, size)
+        })
+    }
 }
 
 impl RpcApi for Client {
@@ -834,15 +1466,63 @@ impl RpcApi for Client {
     ) -> Result<T> {
         let req = self.client.build_request(&cmd, &args);
         if log_enabled!(Debug) {
-            debug!("JSON-RPC request: {}", serde_json::to_string(&req).unwrap());
+            debug!("JSON-RPC request: {}", self.redactions.request_json(&req));
+        }
+
+        let (_permit, queue_wait) = self.limiter.acquire();
+        metrics::instrument(self.metrics(), cmd, queue_wait, &req, |measure| {
+            // Only the stream transport sees the raw response to measure it.
+            let (resp, size) = match self.stream {
+                Some(ref stream) if measure => match stream.send_request(&req) {
+                    Ok((resp, size)) => (Ok(resp), Some(size)),
+                    Err(e) => (Err(e), None),
+                },
+                _ => (self.client.send_request(&req).map_err(Error::from), None),
+            };
+            match resp {
+                Ok(ref resp) if log_enabled!(Debug) => {
+                    debug!("JSON-RPC response: {}", self.redactions.response_json(cmd, resp));
+                }
+                _ => {}
+            }
+            (resp.and_then(|resp| Ok(resp.into_result()?)), size)
+        })
+    }
+
+    /// Send the calls in JSON-RPC batches of at most the maximum batch
+    /// size, see [Client::with_max_batch_size].
+    fn call_batch<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[Vec<serde_json::Value>],
+    ) -> Result<Vec<T>> {
+        let mut results = Vec::with_capacity(args.len());
+        for chunk in args.chunks(self.max_batch_size) {
+            results.extend(self.send_batch(cmd, chunk)?);
         }
+        Ok(results)
+    }
+
+    /// Decode the result while it is received if the client was created
+    /// with [Client::new].
+    fn call_hex<T: Decodable>(&self, cmd: &str, args: &[serde_json::Value]) -> Result<T> {
+        let stream = match self.stream {
+            Some(ref stream) => stream,
+            None => {
+                let hex: String = self.call(cmd, args)?;
+                let bytes: Vec<u8> = FromHex::from_hex(&hex)?;
+                return Ok(bitcoin::consensus::encode::deserialize(&bytes)?);
+            }
+        };
 
-        let resp = self.client.send_request(&req).map_err(Error::from);
-        if log_enabled!(Debug) && resp.is_ok() {
-            let resp = resp.as_ref().unwrap();
-            debug!("JSON-RPC response: {}", serde_json::to_string(resp).unwrap());
+        let req = self.client.build_request(cmd, args);
+        if log_enabled!(Debug) {
+            debug!("JSON-RPC request: {}", self.redactions.request_json(&req));
         }
-        Ok(resp?.into_result()?)
+        let (_permit, queue_wait) = self.limiter.acquire();
+        metrics::instrument(self.metrics(), cmd, queue_wait, &req, |_| {
+            (stream.call_hex(&req), None)
+        })
     }
 }
 
@@ -850,7 +1530,59 @@ impl RpcApi for Client {
 mod tests {
     use super::*;
     use bitcoin;
+    use metrics::PrometheusMetrics;
     use serde_json;
+    use std::sync::Mutex;
+    use testutil::serve;
+
+    #[test]
+    fn test_call_batch_chunks() {
+        let url = serve(
+            |request| serde_json::json!({ "result": request["params"][0], "id": request["id"] }),
+        );
+        let metrics = Arc::new(PrometheusMetrics::new());
+        let client = Client::new(url, Auth::None)
+            .unwrap()
+            .with_metrics(metrics.clone())
+            .with_max_batch_size(2);
+
+        let args: Vec<Vec<serde_json::Value>> = (0..5).map(|i| vec![i.into()]).collect();
+        let results: Vec<u64> = client.call_batch("echo", &args).unwrap();
+        assert_eq!(results, vec![0, 1, 2, 3, 4]);
+        assert!(client.call_batch::<u64>("echo", &[]).unwrap().is_empty());
+        // The calls were sent in 3 batches.
+        assert!(metrics.render().contains("bitcoind_rpc_calls_total{method=\"echo\"} 3\n"));
+    }
+
+    #[test]
+    fn test_get_chain_tx_stats_args() {
+        let params = Arc::new(Mutex::new(Vec::new()));
+        let seen = params.clone();
+        let url = serve(move |request| {
+            seen.lock().unwrap().push(request["params"].clone());
+            serde_json::json!({
+                "result": {
+                    "time": 0, "txcount": 1, "window_block_count": 0,
+                    "window_final_block_hash": bitcoin::BlockHash::default(),
+                },
+                "id": request["id"],
+            })
+        });
+        let client = Client::new(url, Auth::None).unwrap();
+        let hash = bitcoin::BlockHash::default();
+        client.get_chain_tx_stats(None, Some(&hash)).unwrap();
+        client.get_chain_tx_stats(Some(10), None).unwrap();
+        client.get_chain_tx_stats(None, None).unwrap();
+        // A missing `nblocks` is sent as null, so the node fits the window.
+        assert_eq!(
+            *params.lock().unwrap(),
+            vec![
+                serde_json::json!([null, hash]),
+                serde_json::json!([10]),
+                serde_json::json!([]),
+            ]
+        );
+    }
 
     #[test]
     fn test_raw_tx() {
@@ -920,4 +1652,13 @@ mod tests {
     fn test_handle_defaults() {
         test_handle_defaults_inner().unwrap();
     }
+
+    #[test]
+    fn test_trim_trailing_nulls() {
+        let args = [into_json(0).unwrap(), null(), into_json(2).unwrap(), null(), null()];
+        assert_eq!(trim_trailing_nulls(&args), &args[..3]);
+        assert_eq!(trim_trailing_nulls(&args[..2]), &args[..1]);
+        assert!(trim_trailing_nulls(&[null(), null()]).is_empty());
+        assert!(trim_trailing_nulls(&[]).is_empty());
+    }
 }
@@ -0,0 +1,438 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Detecting when several nodes disagree about the chain or the mempool.
+
+use std::collections::{HashMap, HashSet};
+use std::result;
+
+use bitcoin::{BlockHash, Txid};
+
+use client::{Result, RpcApi};
+use error::Error;
+use json::{GetChainTipsResult, GetChainTipsResultStatus};
+
+/// What a node reported during a consistency check.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NodeState {
+    pub name: String,
+    /// The hash of the active chain tip.
+    pub best_block_hash: BlockHash,
+    /// The height of the active chain tip.
+    pub height: u64,
+    pub chain_tips: GetChainTipsResult,
+    pub mempool: HashSet<Txid>,
+}
+
+/// How the best block of a node relates to the best block of the majority.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BestBlockDivergence {
+    /// The node is on the majority chain but lacks the last blocks.
+    Behind(u64),
+    /// The node is on the majority chain and has more blocks.
+    Ahead(u64),
+    /// The node is on another branch.
+    StaleFork,
+    /// The node considers the majority chain invalid.
+    RejectsChain,
+}
+
+/// A disagreement found by a [ConsistencyChecker].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Divergence {
+    /// The node couldn't be queried.
+    Unreachable {
+        node: String,
+        error: String,
+    },
+    /// The best block of the node is not the one of the majority.
+    BestBlock {
+        node: String,
+        best_block_hash: BlockHash,
+        height: u64,
+        kind: BestBlockDivergence,
+    },
+    /// Some nodes consider the branch of the chain tip `hash` invalid while
+    /// others consider it valid.
+    TipValidity {
+        hash: BlockHash,
+        invalid_on: Vec<String>,
+        valid_on: Vec<String>,
+    },
+    /// Transactions that are in the mempool of other nodes are missing from
+    /// the mempool of the node, see [ConsistencyChecker::mempool_quorum].
+    MissingTransactions {
+        node: String,
+        txids: Vec<Txid>,
+    },
+}
+
+/// The result of a consistency check.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ConsistencyReport {
+    /// The states of the reachable nodes.
+    pub nodes: Vec<NodeState>,
+    /// The best block of the majority of the nodes.
+    pub best_block_hash: Option<BlockHash>,
+    pub divergences: Vec<Divergence>,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// Compares the best blocks, chain tips and mempools of several nodes.
+///
+/// The majority is the best block most nodes agree on, ties are broken by
+/// height. By default a transaction is expected in the mempool of every node
+/// as soon as one node has it, so a transaction that is still being relayed
+/// can show up as missing.
+pub struct ConsistencyChecker<'a, C: 'a> {
+    nodes: Vec<(String, &'a C)>,
+    mempool_quorum: usize,
+}
+
+impl<'a, C: RpcApi> ConsistencyChecker<'a, C> {
+    pub fn new() -> ConsistencyChecker<'a, C> {
+        ConsistencyChecker {
+            nodes: Vec::new(),
+            mempool_quorum: 1,
+        }
+    }
+
+    /// Only expect a transaction in the mempool of every node once `nodes`
+    /// nodes have it, to leave time for relaying.
+    pub fn mempool_quorum(mut self, nodes: usize) -> ConsistencyChecker<'a, C> {
+        self.mempool_quorum = nodes;
+        self
+    }
+
+    /// Add a node, `name` identifies it in the report.
+    pub fn add_node(mut self, name: &str, rpc: &'a C) -> ConsistencyChecker<'a, C> {
+        self.nodes.push((name.to_owned(), rpc));
+        self
+    }
+
+    /// Query all nodes and compare them.
+    pub fn check(&self) -> ConsistencyReport {
+        let mut divergences = Vec::new();
+        let mut states = Vec::new();
+        let mut rpcs = Vec::new();
+        for &(ref name, rpc) in &self.nodes {
+            match query_state(name, rpc) {
+                Ok(state) => {
+                    states.push(state);
+                    rpcs.push(rpc);
+                }
+                Err(e) => divergences.push(Divergence::Unreachable {
+                    node: name.clone(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+
+        let majority = majority_best_block(&states);
+        if let Some(reference) = majority {
+            let reference_rpc = rpcs[reference];
+            for (state, &rpc) in states.iter().zip(&rpcs) {
+                if state.best_block_hash == states[reference].best_block_hash {
+                    continue;
+                }
+                let divergence = match classify(state, rpc, &states[reference], reference_rpc) {
+                    Ok(kind) => Divergence::BestBlock {
+                        node: state.name.clone(),
+                        best_block_hash: state.best_block_hash,
+                        height: state.height,
+                        kind,
+                    },
+                    Err(unreachable) => unreachable,
+                };
+                // The reference node can fail for several nodes.
+                if !divergences.contains(&divergence) {
+                    divergences.push(divergence);
+                }
+            }
+        }
+
+        divergences.extend(tip_validity(&states));
+        divergences.extend(missing_transactions(&states, self.mempool_quorum));
+        ConsistencyReport {
+            best_block_hash: majority.map(|i| states[i].best_block_hash),
+            nodes: states,
+            divergences,
+        }
+    }
+}
+
+impl<'a, C: RpcApi> Default for ConsistencyChecker<'a, C> {
+    fn default() -> ConsistencyChecker<'a, C> {
+        ConsistencyChecker::new()
+    }
+}
+
+fn query_state<C: RpcApi>(name: &str, rpc: &C) -> Result<NodeState> {
+    // Both come from the active tip so they refer to the same block.
+    let chain_tips = rpc.get_chain_tips()?;
+    let (best_block_hash, height) = chain_tips
+        .iter()
+        .find(|t| t.status == GetChainTipsResultStatus::Active)
+        .map(|t| (t.hash, t.height))
+        .ok_or(Error::UnexpectedStructure)?;
+    Ok(NodeState {
+        name: name.to_owned(),
+        best_block_hash,
+        height,
+        chain_tips,
+        mempool: rpc.get_raw_mempool()?.into_iter().collect(),
+    })
+}
+
+/// The index of a node with the best block most nodes agree on.
+fn majority_best_block(states: &[NodeState]) -> Option<usize> {
+    let mut votes: HashMap<BlockHash, usize> = HashMap::new();
+    for state in states {
+        *votes.entry(state.best_block_hash).or_insert(0) += 1;
+    }
+    (0..states.len()).max_by(|&a, &b| {
+        let (a, b) = (&states[a], &states[b]);
+        votes[&a.best_block_hash].cmp(&votes[&b.best_block_hash]).then(a.height.cmp(&b.height))
+    })
+}
+
+/// Classify the best block of `state` against the one of `reference`, the
+/// error is reported for the node whose call failed.
+fn classify<C: RpcApi>(
+    state: &NodeState,
+    rpc: &C,
+    reference: &NodeState,
+    reference_rpc: &C,
+) -> result::Result<BestBlockDivergence, Divergence> {
+    let rejected = state.chain_tips.iter().any(|t| {
+        t.hash == reference.best_block_hash && t.status == GetChainTipsResultStatus::Invalid
+    });
+    if rejected {
+        return Ok(BestBlockDivergence::RejectsChain);
+    }
+
+    // Compare the block of the lower of both chains at the same height.
+    if state.height <= reference.height {
+        let hash =
+            reference_rpc.get_block_hash(state.height).map_err(|e| unreachable(reference, e))?;
+        if hash == state.best_block_hash {
+            return Ok(BestBlockDivergence::Behind(reference.height - state.height));
+        }
+    } else {
+        let hash = rpc.get_block_hash(reference.height).map_err(|e| unreachable(state, e))?;
+        if hash == reference.best_block_hash {
+            return Ok(BestBlockDivergence::Ahead(state.height - reference.height));
+        }
+    }
+    Ok(BestBlockDivergence::StaleFork)
+}
+
+fn unreachable(node: &NodeState, error: Error) -> Divergence {
+    Divergence::Unreachable {
+        node: node.name.clone(),
+        error: error.to_string(),
+    }
+}
+
+fn tip_validity(states: &[NodeState]) -> Vec<Divergence> {
+    let mut tips: HashMap<BlockHash, (Vec<String>, Vec<String>)> = HashMap::new();
+    for state in states {
+        for tip in &state.chain_tips {
+            let entry = tips.entry(tip.hash).or_default();
+            match tip.status {
+                GetChainTipsResultStatus::Invalid => entry.0.push(state.name.clone()),
+                GetChainTipsResultStatus::Active | GetChainTipsResultStatus::ValidFork => {
+                    entry.1.push(state.name.clone())
+                }
+                // Not fully validated, no opinion yet.
+                GetChainTipsResultStatus::HeadersOnly | GetChainTipsResultStatus::ValidHeaders => {}
+            }
+        }
+    }
+
+    let mut disputed: Vec<_> = tips
+        .into_iter()
+        .filter(|&(_, (ref invalid_on, ref valid_on))| {
+            !invalid_on.is_empty() && !valid_on.is_empty()
+        })
+        .collect();
+    disputed.sort_by_key(|&(hash, _)| hash);
+    disputed
+        .into_iter()
+        .map(|(hash, (invalid_on, valid_on))| Divergence::TipValidity {
+            hash,
+            invalid_on,
+            valid_on,
+        })
+        .collect()
+}
+
+fn missing_transactions(states: &[NodeState], quorum: usize) -> Vec<Divergence> {
+    let mut counts: HashMap<Txid, usize> = HashMap::new();
+    for state in states {
+        for txid in &state.mempool {
+            *counts.entry(*txid).or_insert(0) += 1;
+        }
+    }
+    let expected: Vec<Txid> =
+        counts.into_iter().filter(|&(_, n)| n >= quorum).map(|(txid, _)| txid).collect();
+
+    states
+        .iter()
+        .filter_map(|state| {
+            let mut txids: Vec<Txid> =
+                expected.iter().filter(|&txid| !state.mempool.contains(txid)).cloned().collect();
+            if txids.is_empty() {
+                return None;
+            }
+            txids.sort();
+            Some(Divergence::MissingTransactions {
+                node: state.name.clone(),
+                txids,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::util::hash::BitcoinHash;
+    use bitcoin::{Amount, Block, Script, Transaction};
+    use mining::coinbase_transaction;
+    use testutil::{chain, rpc_error, FakeNode};
+
+    fn tx(n: u64) -> Transaction {
+        coinbase_transaction(1000 + n, &Script::new(), Amount::ONE_BTC)
+    }
+
+    /// `blocks` followed by `count` blocks of another branch.
+    fn branch(blocks: &[Block], count: u64) -> Vec<Block> {
+        let mut blocks = blocks.to_vec();
+        for _ in 0..count {
+            let mut block = blocks[blocks.len() - 1].clone();
+            block.header.prev_blockhash = block.bitcoin_hash();
+            block.header.nonce += 1;
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    fn fake_node(blocks: Vec<Block>, mempool: &[u64]) -> FakeNode {
+        let mut node = FakeNode::with_blocks(blocks);
+        node.mempool = mempool.iter().map(|&n| tx(n)).collect();
+        node
+    }
+
+    #[test]
+    fn test_consistency_checker() {
+        let main = chain(4);
+        let a = fake_node(main.clone(), &[1, 2]);
+        let b = fake_node(main.clone(), &[1, 2, 3]);
+        let report = ConsistencyChecker::new().add_node("a", &a).add_node("b", &b).check();
+        assert_eq!(report.best_block_hash, Some(a.hash(3)));
+        assert_eq!(report.nodes[0].height, 3);
+        assert_eq!(
+            report.divergences,
+            vec![Divergence::MissingTransactions {
+                node: "a".into(),
+                txids: vec![tx(3).txid()],
+            }]
+        );
+        let report =
+            ConsistencyChecker::new().add_node("a", &a).add_node("b", &b).mempool_quorum(2).check();
+        assert!(report.is_consistent());
+
+        let behind = fake_node(main[..2].to_vec(), &[1, 2]);
+        let mut stale = fake_node(branch(&main[..3], 1), &[1]);
+        stale.forks.push((a.hash(3), 3, "valid-fork"));
+        let mut rejecting = fake_node(branch(&main[..3], 2), &[1, 2]);
+        rejecting.forks.push((a.hash(3), 3, "invalid"));
+        let down = fake_node(main.clone(), &[]);
+        down.set_down(true);
+
+        let report = ConsistencyChecker::new()
+            .add_node("a", &a)
+            .add_node("b", &b)
+            .add_node("behind", &behind)
+            .add_node("stale", &stale)
+            .add_node("rejecting", &rejecting)
+            .add_node("down", &down)
+            .mempool_quorum(3)
+            .check();
+        assert_eq!(report.best_block_hash, Some(a.hash(3)));
+        assert_eq!(report.nodes.len(), 5);
+        assert_eq!(
+            report.divergences,
+            vec![
+                Divergence::Unreachable {
+                    node: "down".into(),
+                    error: "I/O error: connection refused".into(),
+                },
+                Divergence::BestBlock {
+                    node: "behind".into(),
+                    best_block_hash: a.hash(1),
+                    height: 1,
+                    kind: BestBlockDivergence::Behind(2),
+                },
+                Divergence::BestBlock {
+                    node: "stale".into(),
+                    best_block_hash: stale.hash(3),
+                    height: 3,
+                    kind: BestBlockDivergence::StaleFork,
+                },
+                Divergence::BestBlock {
+                    node: "rejecting".into(),
+                    best_block_hash: rejecting.hash(4),
+                    height: 4,
+                    kind: BestBlockDivergence::RejectsChain,
+                },
+                Divergence::TipValidity {
+                    hash: a.hash(3),
+                    invalid_on: vec!["rejecting".into()],
+                    valid_on: vec!["a".into(), "b".into(), "stale".into()],
+                },
+                Divergence::MissingTransactions {
+                    node: "stale".into(),
+                    txids: vec![tx(2).txid()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_consistency_checker_reference_unreachable() {
+        let main = chain(4);
+        let mut a = fake_node(main.clone(), &[]);
+        let mut b = fake_node(main.clone(), &[]);
+        let behind = fake_node(main[..2].to_vec(), &[]);
+        let error = || rpc_error(-1, "getblockhash failed");
+        a.on("getblockhash", move |_| Err(error()));
+        b.on("getblockhash", move |_| Err(error()));
+
+        // The reference node failed, not the node compared to it.
+        let report = ConsistencyChecker::new()
+            .add_node("a", &a)
+            .add_node("b", &b)
+            .add_node("behind", &behind);
+        assert_eq!(
+            report.check().divergences,
+            vec![Divergence::Unreachable {
+                node: "b".into(),
+                error: error().to_string(),
+            }]
+        );
+    }
+}
@@ -13,6 +13,7 @@ use std::{error, fmt, io};
 use bitcoin;
 use bitcoin::secp256k1;
 use bitcoin::hashes::hex;
+use bitcoin::util::merkleblock::MerkleBlockError;
 use jsonrpc;
 use serde_json;
 
@@ -27,6 +28,39 @@ pub enum Error {
     Io(io::Error),
     InvalidAmount(bitcoin::util::amount::ParseAmountError),
     InvalidCookieFile,
+    /// The JSON result had an unexpected structure.
+    UnexpectedStructure,
+    /// The daemon returned an error string.
+    ReturnedError(String),
+    /// A wallet dump file could not be parsed at the given line.
+    InvalidWalletDump(usize),
+    /// A merkle proof failed to verify.
+    InvalidMerkleProof(MerkleBlockError),
+    /// A merkle proof was checked against the header of another block, the
+    /// hash of the block the proof is for is given.
+    MerkleProofBlockMismatch(bitcoin::BlockHash),
+    /// A merkle proof is for a block that is not on the active chain.
+    MerkleProofStaleBlock(bitcoin::BlockHash),
+    /// The header of the given block doesn't have the proof of work it
+    /// claims.
+    InvalidProofOfWork(bitcoin::BlockHash),
+    /// A message signature was not valid base64 or had an invalid header.
+    InvalidMessageSignature,
+    /// A block filter could not be decoded or matched against.
+    BlockFilter(bitcoin::util::bip158::Error),
+    /// The filter header of the block does not commit to its filter and the
+    /// previous filter header.
+    InvalidFilterHeader(bitcoin::BlockHash),
+    /// The transaction was not found in any of the places that were tried.
+    TransactionNotFound(bitcoin::Txid),
+    /// A `ReplayClient` has no recorded response for the call.
+    NoRecordedResponse(String),
+    /// The block fetched at the given height doesn't build on the block
+    /// before it, the chain was reorganized during the download.
+    ChainReorganized(u64),
+    /// The worker fetching the block at the given height stopped without
+    /// sending it.
+    BlockFetchInterrupted(u64),
 }
 
 impl From<jsonrpc::error::Error> for Error {
@@ -71,6 +105,18 @@ impl From<bitcoin::util::amount::ParseAmountError> for Error {
     }
 }
 
+impl From<MerkleBlockError> for Error {
+    fn from(e: MerkleBlockError) -> Error {
+        Error::InvalidMerkleProof(e)
+    }
+}
+
+impl From<bitcoin::util::bip158::Error> for Error {
+    fn from(e: bitcoin::util::bip158::Error) -> Error {
+        Error::BlockFilter(e)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -82,6 +128,34 @@ impl fmt::Display for Error {
             Error::Io(ref e) => write!(f, "I/O error: {}", e),
             Error::InvalidAmount(ref e) => write!(f, "invalid amount: {}", e),
             Error::InvalidCookieFile => write!(f, "invalid cookie file"),
+            Error::UnexpectedStructure => write!(f, "the JSON result had an unexpected structure"),
+            Error::ReturnedError(ref s) => write!(f, "the daemon returned an error string: {}", s),
+            Error::InvalidWalletDump(line) => write!(f, "invalid wallet dump at line {}", line),
+            Error::InvalidMerkleProof(ref e) => {
+                write!(f, "invalid merkle proof: ")?;
+                match *e {
+                    MerkleBlockError::MerkleRootMismatch => write!(f, "merkle root mismatch"),
+                    MerkleBlockError::NoTransactions => write!(f, "no transactions"),
+                    MerkleBlockError::TooManyTransactions => write!(f, "too many transactions"),
+                    MerkleBlockError::BadFormat(ref s) => write!(f, "{}", s),
+                }
+            }
+            Error::MerkleProofBlockMismatch(ref h) => {
+                write!(f, "the merkle proof is for another block: {}", h)
+            }
+            Error::MerkleProofStaleBlock(ref h) => {
+                write!(f, "the merkle proof is for block {} not on the active chain", h)
+            }
+            Error::InvalidProofOfWork(ref h) => write!(f, "invalid proof of work for {}", h),
+            Error::InvalidMessageSignature => write!(f, "invalid message signature"),
+            Error::BlockFilter(ref e) => write!(f, "block filter error: {}", e),
+            Error::InvalidFilterHeader(ref h) => write!(f, "invalid filter header for {}", h),
+            Error::TransactionNotFound(ref t) => write!(f, "transaction {} not found", t),
+            Error::NoRecordedResponse(ref s) => write!(f, "no recorded response: {}", s),
+            Error::ChainReorganized(h) => write!(f, "chain reorganized at height {}", h),
+            Error::BlockFetchInterrupted(h) => {
+                write!(f, "block fetch stopped before height {}", h)
+            }
         }
     }
 }
@@ -99,6 +173,7 @@ impl error::Error for Error {
             Error::BitcoinSerialization(ref e) => Some(e),
             Error::Secp256k1(ref e) => Some(e),
             Error::Io(ref e) => Some(e),
+            Error::BlockFilter(ref e) => Some(e),
             _ => None,
         }
     }
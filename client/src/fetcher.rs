@@ -0,0 +1,260 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Parallel download of block ranges.
+
+use std::ops::Range;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use bitcoin::util::hash::BitcoinHash;
+use bitcoin::{Block, BlockHash};
+
+use client::{Result, RpcApi};
+use error::Error;
+
+/// Downloads ranges of blocks of the active chain using several threads.
+///
+/// Blocks are yielded in height order. Every worker only gets a few blocks
+/// ahead of the consumer, so a slow consumer throttles the download. If the
+/// chain is reorganized during the download, the first block that doesn't
+/// build on the one before is reported as [Error::ChainReorganized].
+pub struct BlockFetcher<C> {
+    rpc: Arc<C>,
+    workers: usize,
+    buffer: usize,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl<C: RpcApi + Send + Sync + 'static> BlockFetcher<C> {
+    /// Create a fetcher that uses `workers` threads.
+    ///
+    /// By default every worker buffers up to 4 blocks and failed requests
+    /// are retried 3 times after 100 milliseconds.
+    pub fn new(rpc: Arc<C>, workers: usize) -> BlockFetcher<C> {
+        BlockFetcher {
+            rpc,
+            workers: workers.max(1),
+            buffer: 4,
+            retries: 3,
+            retry_delay: Duration::from_millis(100),
+        }
+    }
+
+    /// Set the number of blocks every worker may fetch ahead of the consumer.
+    pub fn buffer(mut self, blocks: usize) -> BlockFetcher<C> {
+        self.buffer = blocks;
+        self
+    }
+
+    /// Set how often and after which delay a failed request is retried.
+    pub fn retries(mut self, retries: u32, delay: Duration) -> BlockFetcher<C> {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Start downloading the blocks at the heights in `range`.
+    ///
+    /// The workers stop when the returned iterator is dropped.
+    pub fn fetch(&self, range: Range<u64>) -> FetchedBlocks {
+        let workers = (self.workers as u64).min(range.end.saturating_sub(range.start)).max(1);
+        let mut receivers = Vec::with_capacity(workers as usize);
+        for worker in 0..workers {
+            let (sender, receiver) = sync_channel(self.buffer);
+            receivers.push(receiver);
+
+            let rpc = self.rpc.clone();
+            let (retries, retry_delay) = (self.retries, self.retry_delay);
+            let mut height = range.start + worker;
+            let end = range.end;
+            thread::spawn(move || {
+                while height < end {
+                    let result = with_retries(retries, retry_delay, || {
+                        let hash = rpc.get_block_hash(height)?;
+                        rpc.get_block(&hash)
+                    });
+                    let failed = result.is_err();
+                    // The consumer hung up or won't read any further.
+                    if sender.send(result.map(|block| (height, block))).is_err() || failed {
+                        return;
+                    }
+                    height += workers;
+                }
+            });
+        }
+
+        FetchedBlocks {
+            receivers,
+            next: range.start,
+            prev: None,
+            start: range.start,
+            end: range.end,
+            failed: false,
+        }
+    }
+}
+
+fn with_retries<T, F: FnMut() -> Result<T>>(retries: u32, delay: Duration, mut f: F) -> Result<T> {
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Err(e) if attempt < retries => {
+                warn!("block request failed, retrying: {}", e);
+                attempt += 1;
+                thread::sleep(delay);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The blocks downloaded by a [BlockFetcher], in height order.
+///
+/// After an error that persisted through all retries no more blocks are
+/// yielded. A worker that stops without sending its next block, for example
+/// because it panicked, is reported as [Error::BlockFetchInterrupted].
+pub struct FetchedBlocks {
+    receivers: Vec<Receiver<Result<(u64, Block)>>>,
+    next: u64,
+    /// The hash of the last block yielded.
+    prev: Option<BlockHash>,
+    start: u64,
+    end: u64,
+    failed: bool,
+}
+
+impl Iterator for FetchedBlocks {
+    type Item = Result<(u64, Block)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.next >= self.end {
+            return None;
+        }
+        let worker = ((self.next - self.start) % self.receivers.len() as u64) as usize;
+        // Workers only hang up early after sending an error, or on a panic.
+        let mut result = match self.receivers[worker].recv() {
+            Ok(result) => result,
+            Err(_) => Err(Error::BlockFetchInterrupted(self.next)),
+        };
+        match result {
+            Ok((height, ref block)) if self.prev.is_some() => {
+                if Some(block.header.prev_blockhash) != self.prev {
+                    result = Err(Error::ChainReorganized(height));
+                } else {
+                    self.prev = Some(block.bitcoin_hash());
+                }
+            }
+            Ok((_, ref block)) => self.prev = Some(block.bitcoin_hash()),
+            Err(_) => {}
+        }
+        self.failed = result.is_err();
+        self.next += 1;
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+    use testutil::{chain, FakeNode};
+
+    /// A node with `count` blocks whose requests for blocks at heights in
+    /// `flaky` fail once and at height `broken` always fail.
+    fn fake_node(count: u64, flaky: &[u64], broken: Option<u64>) -> FakeNode {
+        let mut node = FakeNode::new(count);
+        let hashes: Vec<_> = node.blocks.iter().map(|b| b.bitcoin_hash()).collect();
+        let flaky = Mutex::new(flaky.iter().cloned().collect::<HashSet<u64>>());
+        node.intercept(move |cmd, args| {
+            let height = match cmd {
+                "getblockhash" => args[0].as_u64().unwrap(),
+                "getblock" => {
+                    let hash = serde_json::from_value(args[0].clone()).unwrap();
+                    hashes.iter().position(|h| *h == hash).unwrap() as u64
+                }
+                _ => return None,
+            };
+            if Some(height) == broken || flaky.lock().unwrap().remove(&height) {
+                return Some(Err(Error::ReturnedError(format!("failed to fetch {}", height))));
+            }
+            None
+        });
+        node
+    }
+
+    #[test]
+    fn test_block_fetcher() {
+        let rpc = Arc::new(fake_node(50, &[3, 17, 18], None));
+        let fetcher =
+            BlockFetcher::new(rpc.clone(), 4).buffer(2).retries(1, Duration::from_millis(1));
+
+        let fetched: Vec<(u64, Block)> = fetcher.fetch(5..45).map(|r| r.unwrap()).collect();
+        assert_eq!(fetched.len(), 40);
+        for (i, &(height, ref block)) in fetched.iter().enumerate() {
+            assert_eq!(height, 5 + i as u64);
+            assert_eq!(*block, rpc.blocks[height as usize]);
+        }
+
+        assert_eq!(fetcher.fetch(10..10).count(), 0);
+        assert_eq!(fetcher.fetch(48..50).count(), 2);
+    }
+
+    #[test]
+    fn test_block_fetcher_failure() {
+        let rpc = Arc::new(fake_node(20, &[], Some(7)));
+        let fetcher = BlockFetcher::new(rpc, 3).retries(2, Duration::from_millis(1));
+
+        let results: Vec<_> = fetcher.fetch(0..20).collect();
+        assert_eq!(results.len(), 8);
+        assert!(results[..7].iter().all(|r| r.is_ok()));
+        assert!(results[7].is_err());
+    }
+
+    #[test]
+    fn test_block_fetcher_reorg() {
+        let mut blocks = chain(20);
+        blocks[10].header.prev_blockhash = Default::default();
+        let fetcher = BlockFetcher::new(Arc::new(FakeNode::with_blocks(blocks)), 3);
+
+        let results: Vec<_> = fetcher.fetch(5..20).collect();
+        assert_eq!(results.len(), 6);
+        assert!(results[..5].iter().all(|r| r.is_ok()));
+        match results[5] {
+            Err(Error::ChainReorganized(10)) => {}
+            ref r => panic!("unexpected result: {:?}", r),
+        }
+        // The first block of a range is not checked.
+        assert_eq!(fetcher.fetch(11..20).filter(|r| r.is_ok()).count(), 9);
+    }
+
+    #[test]
+    fn test_block_fetcher_worker_panic() {
+        let mut node = FakeNode::new(20);
+        node.intercept(|cmd, args| {
+            if cmd == "getblockhash" && args[0] == 12 {
+                panic!("failed to fetch 12");
+            }
+            None
+        });
+        let fetcher = BlockFetcher::new(Arc::new(node), 3);
+        let results: Vec<_> = fetcher.fetch(5..20).collect();
+        assert_eq!(results.len(), 8);
+        match results[7] {
+            Err(Error::BlockFetchInterrupted(12)) => {}
+            ref r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,343 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Scanning the chain for scripts using compact block filters (BIP158).
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+use bitcoin;
+use bitcoin::hashes::Hash;
+use bitcoin::{OutPoint, Script, Transaction};
+
+use client::{Result, RpcApi};
+use error::Error;
+
+/// Finds the transactions of a set of scripts in a range of blocks.
+///
+/// Only the filters of all blocks are downloaded, full blocks are only
+/// requested when their filter matches. This requires the node to run with
+/// `-blockfilterindex`.
+///
+/// Spends of outputs paying to the scripts are found from the outputs the
+/// blocks spend, see [RpcApi::get_block_verbose_tx_with_prevouts]. Nodes
+/// before v23 don't give those, then only spends of outputs found earlier in
+/// the same scan are found.
+pub struct FilterScanner<'a, C: 'a> {
+    rpc: &'a C,
+    scripts: HashSet<Script>,
+}
+
+/// The relevant transactions of a block found by a [FilterScanner].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FilterMatch {
+    pub height: u64,
+    pub block_hash: bitcoin::BlockHash,
+    /// The transactions that pay to one of the scripts or spend an output
+    /// that does.
+    pub transactions: Vec<Transaction>,
+}
+
+impl<'a, C: RpcApi> FilterScanner<'a, C> {
+    pub fn new(rpc: &'a C, scripts: Vec<Script>) -> FilterScanner<'a, C> {
+        FilterScanner {
+            rpc,
+            scripts: scripts.into_iter().collect(),
+        }
+    }
+
+    /// Also scan for the scripts of the addresses derived from `descriptor`,
+    /// see [RpcApi::derive_addresses].
+    pub fn add_descriptor(&mut self, descriptor: &str, range: Option<[u32; 2]>) -> Result<()> {
+        let addresses = self.rpc.derive_addresses(descriptor, range)?;
+        self.scripts.extend(addresses.iter().map(|a| a.script_pubkey()));
+        Ok(())
+    }
+
+    /// Scan the blocks at the heights in `range`.
+    ///
+    /// Every filter is checked against the chain of filter headers, which is
+    /// anchored at the filter header of the block before the range.
+    pub fn scan<'s>(&'s self, range: Range<u64>) -> FilterScan<'s, 'a, C> {
+        FilterScan {
+            scanner: self,
+            range,
+            previous_header: None,
+            outpoints: HashSet::new(),
+            failed: false,
+        }
+    }
+}
+
+/// The iterator over the matches of a [FilterScanner].
+///
+/// Blocks whose filter matched but that contain no relevant transaction
+/// (BIP158 filters have false positives) are skipped. After an error no
+/// more matches are yielded.
+pub struct FilterScan<'s, 'a: 's, C: 'a> {
+    scanner: &'s FilterScanner<'a, C>,
+    range: Range<u64>,
+    previous_header: Option<bitcoin::FilterHash>,
+    outpoints: HashSet<OutPoint>,
+    failed: bool,
+}
+
+impl<'s, 'a, C: RpcApi> FilterScan<'s, 'a, C> {
+    fn scan_block(&mut self, height: u64) -> Result<Option<FilterMatch>> {
+        let rpc = self.scanner.rpc;
+        let previous_header = match self.previous_header {
+            Some(header) => header,
+            None if height == 0 => bitcoin::FilterHash::from_inner([0; 32]),
+            None => rpc.get_block_filter(&rpc.get_block_hash(height - 1)?)?.header,
+        };
+
+        let block_hash = rpc.get_block_hash(height)?;
+        let filter = rpc.get_block_filter(&block_hash)?;
+        if !filter.check_header(&previous_header) {
+            return Err(Error::InvalidFilterHeader(block_hash));
+        }
+        self.previous_header = Some(filter.header);
+
+        // Spends match too, filters include the scripts of spent outputs.
+        let mut scripts = self.scanner.scripts.iter().map(|s| s.as_bytes());
+        if !filter.into_filter().match_any(&block_hash, &mut scripts)? {
+            return Ok(None);
+        }
+
+        let block = rpc.get_block_verbose_tx_with_prevouts(&block_hash)?;
+        let mut transactions = Vec::new();
+        for info in &block.tx {
+            let tx = info.transaction()?;
+            let spends = tx.input.iter().zip(&info.vin).any(|(input, vin)| {
+                self.outpoints.contains(&input.previous_output)
+                    || vin.prevout.iter().any(|prevout| {
+                        let script = Script::from(prevout.script_pub_key.hex.clone());
+                        self.scanner.scripts.contains(&script)
+                    })
+            });
+            let mut pays = false;
+            for (vout, output) in tx.output.iter().enumerate() {
+                if self.scanner.scripts.contains(&output.script_pubkey) {
+                    self.outpoints.insert(OutPoint::new(tx.txid(), vout as u32));
+                    pays = true;
+                }
+            }
+            if spends || pays {
+                transactions.push(tx);
+            }
+        }
+
+        if transactions.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(FilterMatch {
+            height,
+            block_hash,
+            transactions,
+        }))
+    }
+}
+
+impl<'s, 'a, C: RpcApi> Iterator for FilterScan<'s, 'a, C> {
+    type Item = Result<FilterMatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed {
+            let height = self.range.next()?;
+            match self.scan_block(height) {
+                Ok(None) => continue,
+                Ok(Some(m)) => return Some(Ok(m)),
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::consensus::encode::serialize;
+    use bitcoin::hashes::hex::ToHex;
+    use bitcoin::util::bip158::BlockFilter;
+    use bitcoin::util::hash::BitcoinHash;
+    use bitcoin::{Amount, Block, BlockHash, FilterHash, Network, TxIn, TxOut};
+    use mining::coinbase_transaction;
+    use serde_json;
+    use std::collections::HashMap;
+    use testutil::FakeNode;
+
+    /// The filters and filter headers of the blocks of a chain.
+    type Filters = Vec<(BlockFilter, FilterHash)>;
+    /// The scripts of the outputs of a chain.
+    type Scripts = HashMap<OutPoint, Script>;
+
+    /// Build a chain where every block pays to the given script and
+    /// optionally spends an output of an earlier block, with the filters and
+    /// filter headers of the blocks and the scripts of their outputs.
+    fn chain(blocks: &[(Script, Option<OutPoint>)]) -> (Vec<Block>, Filters, Scripts) {
+        let mut chain = (Vec::new(), Vec::new(), HashMap::new());
+        let mut header = FilterHash::from_inner([0; 32]);
+        for (height, &(ref payout, spend)) in blocks.iter().enumerate() {
+            let mut block = genesis_block(Network::Regtest);
+            block.txdata = vec![coinbase_transaction(height as u64, payout, Amount::from_sat(50))];
+            if let Some(outpoint) = spend {
+                let mut tx = block.txdata[0].clone();
+                tx.input = vec![TxIn {
+                    previous_output: outpoint,
+                    ..tx.input[0].clone()
+                }];
+                tx.output = vec![TxOut {
+                    value: 40,
+                    script_pubkey: Script::from(vec![0x51]),
+                }];
+                block.txdata.push(tx);
+            }
+            block.header.merkle_root = block.merkle_root();
+
+            for tx in &block.txdata {
+                for (vout, output) in tx.output.iter().enumerate() {
+                    let outpoint = OutPoint::new(tx.txid(), vout as u32);
+                    chain.2.insert(outpoint, output.script_pubkey.clone());
+                }
+            }
+            let filter =
+                BlockFilter::new_script_filter(&block, |o| Ok(chain.2[o].clone())).unwrap();
+            header = filter.filter_id(&header);
+            chain.1.push((filter, header));
+            chain.0.push(block);
+        }
+        chain
+    }
+
+    /// A block as returned by "getblock" with verbosity 3, with only the
+    /// fields the scanner uses filled in.
+    fn verbose_block(block: &Block, scripts: &Scripts) -> serde_json::Value {
+        let script = |s: &Script| serde_json::json!({ "asm": "", "hex": s.as_bytes().to_hex() });
+        let txs: Vec<_> = block
+            .txdata
+            .iter()
+            .map(|tx| {
+                let vin: Vec<_> = tx
+                    .input
+                    .iter()
+                    .map(|input| match scripts.get(&input.previous_output) {
+                        Some(spent) => serde_json::json!({
+                            "sequence": input.sequence,
+                            "txid": input.previous_output.txid,
+                            "vout": input.previous_output.vout,
+                            "prevout": {
+                                "generated": true, "height": 0, "value": 0.0,
+                                "scriptPubKey": script(spent),
+                            },
+                        }),
+                        None => serde_json::json!({ "sequence": input.sequence, "coinbase": "" }),
+                    })
+                    .collect();
+                serde_json::json!({
+                    "txid": tx.txid(), "hash": tx.wtxid(),
+                    "size": 0, "vsize": 0, "weight": 0, "version": 1, "locktime": 0,
+                    "vin": vin, "vout": [],
+                    "hex": serialize(tx).to_hex(),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "hash": block.bitcoin_hash(), "merkleroot": block.header.merkle_root,
+            "confirmations": 1, "size": 0, "weight": 0, "height": 0, "version": 1,
+            "time": 0, "nonce": 0, "bits": "", "difficulty": 1, "chainwork": "",
+            "nTx": txs.len(), "tx": txs,
+        })
+    }
+
+    /// A node with a block filter index.
+    fn fake_node(blocks: Vec<Block>, filters: Filters, scripts: Scripts) -> FakeNode {
+        let hashes: Vec<BlockHash> = blocks.iter().map(|b| b.bitcoin_hash()).collect();
+        let mut node = FakeNode::with_blocks(blocks.clone());
+        node.intercept(move |cmd, args| {
+            if cmd != "getblock" || args[1] != 3 {
+                return None;
+            }
+            let hash: BlockHash = serde_json::from_value(args[0].clone()).unwrap();
+            let block = blocks.iter().find(|b| b.bitcoin_hash() == hash)?;
+            Some(Ok(verbose_block(block, &scripts)))
+        });
+        node.on("getblockfilter", move |args| {
+            let hash: BlockHash = serde_json::from_value(args[0].clone())?;
+            let height = hashes.iter().position(|h| *h == hash).unwrap();
+            let (ref filter, ref header) = filters[height];
+            Ok(serde_json::json!({
+                "filter": filter.content.to_hex(),
+                "header": header,
+            }))
+        });
+        node
+    }
+
+    /// A P2WPKH script with a 20 byte program of `byte`s.
+    fn script(byte: u8) -> Script {
+        let mut bytes = vec![0x00, 0x14];
+        bytes.extend_from_slice(&[byte; 20]);
+        Script::from(bytes)
+    }
+
+    #[test]
+    fn test_filter_scanner() {
+        let (watched, other) = (script(1), script(2));
+        let coinbase_1 = coinbase_transaction(1, &watched, Amount::from_sat(50)).txid();
+        let (blocks, filters, scripts) = chain(&[
+            (other.clone(), None),
+            (watched.clone(), None),
+            (other.clone(), None),
+            (other.clone(), Some(OutPoint::new(coinbase_1, 0))),
+            (other.clone(), None),
+        ]);
+        let node = fake_node(blocks, filters, scripts);
+
+        let scanner = FilterScanner::new(&node, vec![watched]);
+        let matches: Vec<FilterMatch> = scanner.scan(0..5).map(|m| m.unwrap()).collect();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].height, 1);
+        assert_eq!(matches[0].transactions[0].txid(), coinbase_1);
+        assert_eq!(matches[1].height, 3);
+        assert_eq!(matches[1].transactions.len(), 1);
+        assert_eq!(matches[1].transactions[0].input[0].previous_output.txid, coinbase_1);
+
+        // Starting later anchors the header chain at the previous block, the
+        // spend of an output from before the range is still found.
+        let matches: Vec<FilterMatch> = scanner.scan(2..5).map(|m| m.unwrap()).collect();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].height, 3);
+        assert_eq!(matches[0].transactions[0].input[0].previous_output.txid, coinbase_1);
+    }
+
+    #[test]
+    fn test_filter_scanner_invalid_header() {
+        let (blocks, mut filters, scripts) =
+            chain(&[(script(1), None), (script(2), None), (script(3), None)]);
+        filters[1].1 = FilterHash::from_inner([1; 32]);
+        let node = fake_node(blocks, filters, scripts);
+
+        let scanner = FilterScanner::new(&node, vec![script(1)]);
+        let results: Vec<_> = scanner.scan(0..3).collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        match results[1] {
+            Err(Error::InvalidFilterHeader(hash)) => {
+                assert_eq!(hash, node.hash(1))
+            }
+            ref r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
@@ -18,21 +18,56 @@
 
 #[macro_use]
 extern crate log;
+extern crate base64;
+extern crate hyper;
 extern crate jsonrpc;
 #[allow(unused)]
 #[macro_use] // `macro_use` is needed for v1.24.0 compilation.
 extern crate serde;
 extern crate serde_json;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 
 pub extern crate bitcoincore_rpc_json;
 pub use bitcoincore_rpc_json as json;
 pub use json::bitcoin;
 pub use json::num_bigint;
 
+mod cache;
 mod client;
+mod consistency;
 mod error;
+mod fetcher;
+mod filter;
+mod limit;
+mod merkle;
+mod message;
+mod metrics;
+mod mining;
+mod multi;
 mod queryable;
+mod record;
+mod redact;
+mod resolver;
+mod stream;
+#[cfg(test)]
+mod testutil;
+mod wallet;
 
+pub use cache::*;
 pub use client::*;
+pub use consistency::*;
 pub use error::Error;
+pub use fetcher::*;
+pub use filter::*;
+pub use merkle::*;
+pub use message::*;
+pub use metrics::{CallInfo, Metrics, PrometheusMetrics};
+pub use mining::*;
+pub use multi::*;
 pub use queryable::*;
+pub use record::*;
+pub use redact::{RedactionTable, REDACTED};
+pub use resolver::*;
+pub use stream::decode_hex_result;
+pub use wallet::*;
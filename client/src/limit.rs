@@ -0,0 +1,169 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Rate limiting and a cap on concurrent calls, which make calls wait
+//! instead of overflowing the node's `-rpcworkqueue`.
+
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Queues calls according to an optional rate limit and an optional
+/// maximum number of calls in flight.
+pub(crate) struct Limiter {
+    bucket: Option<Mutex<TokenBucket>>,
+    slots: Option<Slots>,
+}
+
+struct TokenBucket {
+    /// Tokens added per second.
+    rate: f64,
+    burst: f64,
+    /// Negative when calls have reserved tokens that are not there yet.
+    tokens: f64,
+    last: Instant,
+}
+
+struct Slots {
+    free: Mutex<usize>,
+    freed: Condvar,
+}
+
+/// Permission for one call, frees its slot when dropped.
+pub(crate) struct Permit<'a> {
+    slots: Option<&'a Slots>,
+}
+
+impl Limiter {
+    /// A limiter that lets all calls through at once.
+    pub(crate) fn new() -> Limiter {
+        Limiter {
+            bucket: None,
+            slots: None,
+        }
+    }
+
+    /// Allow `rate` calls per second on average and bursts of up to `burst`
+    /// calls.
+    pub(crate) fn set_rate(&mut self, rate: f64, burst: u32) {
+        assert!(rate > 0.0, "the rate limit must be positive");
+        let burst = f64::from(burst.max(1));
+        self.bucket = Some(Mutex::new(TokenBucket {
+            rate,
+            burst,
+            tokens: burst,
+            last: Instant::now(),
+        }));
+    }
+
+    /// Allow at most `calls` calls at the same time.
+    pub(crate) fn set_max_in_flight(&mut self, calls: usize) {
+        self.slots = Some(Slots {
+            free: Mutex::new(calls.max(1)),
+            freed: Condvar::new(),
+        });
+    }
+
+    /// Wait until a call may be made, returns how long that took.
+    pub(crate) fn acquire<'a>(&'a self) -> (Permit<'a>, Duration) {
+        let start = Instant::now();
+        if let Some(ref bucket) = self.bucket {
+            // Every call reserves a token, so calls are let through in the
+            // order they arrived.
+            let wait = {
+                let mut bucket = bucket.lock().unwrap();
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.last);
+                let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+                bucket.tokens = (bucket.tokens + elapsed * bucket.rate).min(bucket.burst);
+                bucket.last = now;
+                bucket.tokens -= 1.0;
+                if bucket.tokens < 0.0 {
+                    -bucket.tokens / bucket.rate
+                } else {
+                    0.0
+                }
+            };
+            if wait > 0.0 {
+                thread::sleep(Duration::from_nanos((wait * 1e9) as u64));
+            }
+        }
+
+        if let Some(ref slots) = self.slots {
+            let mut free = slots.free.lock().unwrap();
+            while *free == 0 {
+                free = slots.freed.wait(free).unwrap();
+            }
+            *free -= 1;
+        }
+        let permit = Permit {
+            slots: self.slots.as_ref(),
+        };
+        (permit, start.elapsed())
+    }
+}
+
+impl<'a> Drop for Permit<'a> {
+    fn drop(&mut self) {
+        if let Some(slots) = self.slots {
+            *slots.free.lock().unwrap() += 1;
+            slots.freed.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_rate_limit() {
+        let mut limiter = Limiter::new();
+        assert_eq!(limiter.acquire().1.as_secs(), 0);
+
+        limiter.set_rate(100.0, 2);
+        let start = Instant::now();
+        let waits: Vec<Duration> = (0..6).map(|_| limiter.acquire().1).collect();
+        // The burst goes through at once, the other 4 calls wait 10ms each.
+        assert!(waits[0] < Duration::from_millis(5));
+        assert!(waits[1] < Duration::from_millis(5));
+        assert!(waits[2] >= Duration::from_millis(5));
+        assert!(start.elapsed() >= Duration::from_millis(35));
+    }
+
+    #[test]
+    fn test_max_in_flight() {
+        let mut limiter = Limiter::new();
+        limiter.set_max_in_flight(2);
+        let limiter = Arc::new(limiter);
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_seen = Arc::new(AtomicUsize::new(0));
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let (limiter, in_flight, max_seen) =
+                    (limiter.clone(), in_flight.clone(), max_seen.clone());
+                thread::spawn(move || {
+                    let (_permit, _) = limiter.acquire();
+                    let n = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_seen.fetch_max(n, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(5));
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(max_seen.load(Ordering::SeqCst), 2);
+    }
+}
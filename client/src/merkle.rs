@@ -0,0 +1,141 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Local verification of the merkle proofs returned by `gettxoutproof`.
+
+use bitcoin;
+use bitcoin::util::hash::BitcoinHash;
+use bitcoin::{BlockHeader, MerkleBlock};
+
+use client::{Result, RpcApi};
+use error::Error;
+
+/// Verify a merkle proof as returned by [RpcApi::get_tx_out_proof] against
+/// a known block header and return the txids it proves to be in the block.
+pub fn verify_merkle_proof(proof: &[u8], header: &BlockHeader) -> Result<Vec<bitcoin::Txid>> {
+    let merkle_block: MerkleBlock = bitcoin::consensus::encode::deserialize(proof)?;
+    if merkle_block.header != *header {
+        return Err(Error::MerkleProofBlockMismatch(merkle_block.header.bitcoin_hash()));
+    }
+
+    let mut txids = Vec::new();
+    let mut indexes = Vec::new();
+    merkle_block.extract_matches(&mut txids, &mut indexes)?;
+    Ok(txids)
+}
+
+/// Verify a merkle proof as returned by [RpcApi::get_tx_out_proof] without
+/// trusting the node's [RpcApi::verify_tx_out_proof].
+///
+/// Only the header of the block the proof claims to be for is fetched from
+/// the node, the proof itself is checked locally against that header. Like
+/// `verifytxoutproof`, proofs for blocks that are not on the active chain are
+/// rejected. The proof of work of the header is checked too.
+pub fn verify_tx_out_proof_locally<C: RpcApi>(rpc: &C, proof: &[u8]) -> Result<Vec<bitcoin::Txid>> {
+    let merkle_block: MerkleBlock = bitcoin::consensus::encode::deserialize(proof)?;
+    let block_hash = merkle_block.header.bitcoin_hash();
+    // Blocks of stale forks have -1 confirmations.
+    if rpc.get_block_header_verbose(&block_hash)?.confirmations < 0 {
+        return Err(Error::MerkleProofStaleBlock(block_hash));
+    }
+    let header = rpc.get_block_header_raw(&block_hash)?;
+    if header.validate_pow(&header.target()).is_err() {
+        return Err(Error::InvalidProofOfWork(block_hash));
+    }
+    verify_merkle_proof(proof, &header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::hex::FromHex;
+    use bitcoin::util::merkleblock::MerkleBlockError;
+    use bitcoin::Block;
+    use serde_json;
+    use testutil::FakeNode;
+
+    // Block 80000, with two transactions.
+    const BLOCK: &str = "01000000ba8b9cda965dd8e536670f9ddec10e53aab14b20bacad27b9137190000000000190760b278fe7b8565fda3b968b918d5fd997f993b23674c0af3b6fde300b38f33a5914ce6ed5b1b01e32f570201000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0704e6ed5b1b014effffffff0100f2052a01000000434104b68a50eaa0287eff855189f949c1c6e5f58b37c88231373d8a59809cbae83059cc6469d65c665ccfd1cfeb75c6e8e19413bba7fbff9bc762419a76d87b16086eac000000000100000001a6b97044d03da79c005b20ea9c0e1a6d9dc12d9f7b91a5911c9030a439eed8f5000000004948304502206e21798a42fae0e854281abd38bacd1aeed3ee3738d9e1446618c4571d1090db022100e2ac980643b0b82c0e88ffdfec6b64e3e6ba35e7ba5fdd7d5d6cc8d25c6b241501ffffffff0100f2052a010000001976a914404371705fa9bd789a2fcd52d2c580b65d35549d88ac00000000";
+
+    #[test]
+    fn test_verify_merkle_proof() {
+        let block: Block =
+            bitcoin::consensus::encode::deserialize(&Vec::<u8>::from_hex(BLOCK).unwrap()).unwrap();
+        let txid = block.txdata[1].txid();
+        let merkle_block = MerkleBlock::from_block(&block, &vec![txid].into_iter().collect());
+        let proof = bitcoin::consensus::encode::serialize(&merkle_block);
+
+        assert_eq!(verify_merkle_proof(&proof, &block.header).unwrap(), vec![txid]);
+
+        let mut other = block.header;
+        other.nonce += 1;
+        match verify_merkle_proof(&proof, &other) {
+            Err(Error::MerkleProofBlockMismatch(hash)) => assert_eq!(hash, block.bitcoin_hash()),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        // A proof whose header commits to a different merkle root.
+        let mut forged = merkle_block;
+        forged.header.merkle_root = Default::default();
+        let proof = bitcoin::consensus::encode::serialize(&forged);
+        match verify_merkle_proof(&proof, &forged.header) {
+            Err(Error::InvalidMerkleProof(MerkleBlockError::MerkleRootMismatch)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+        let err = verify_merkle_proof(&proof, &forged.header).unwrap_err();
+        assert_eq!(err.to_string(), "invalid merkle proof: merkle root mismatch");
+    }
+
+    /// A node with `block`, which has `confirmations` confirmations.
+    fn fake_node(block: Block, confirmations: i32) -> FakeNode {
+        let mut node = FakeNode::with_blocks(vec![block.clone()]);
+        node.intercept(move |cmd, args| {
+            if cmd != "getblockheader" || args[1] == false {
+                return None;
+            }
+            Some(Ok(serde_json::json!({
+                "hash": block.bitcoin_hash(),
+                "confirmations": confirmations,
+                "height": 80000, "version": 1, "merkleroot": block.header.merkle_root,
+                "time": 0, "nonce": 0, "bits": "", "difficulty": 1, "chainwork": "", "nTx": 2,
+            })))
+        });
+        node
+    }
+
+    #[test]
+    fn test_verify_tx_out_proof_locally() {
+        let mut block: Block =
+            bitcoin::consensus::encode::deserialize(&Vec::<u8>::from_hex(BLOCK).unwrap()).unwrap();
+        let txid = block.txdata[1].txid();
+        let proof = |block: &Block| {
+            let merkle_block = MerkleBlock::from_block(block, &vec![txid].into_iter().collect());
+            bitcoin::consensus::encode::serialize(&merkle_block)
+        };
+
+        let node = fake_node(block.clone(), 1);
+        assert_eq!(verify_tx_out_proof_locally(&node, &proof(&block)).unwrap(), vec![txid]);
+
+        // A block on a stale fork.
+        let node = fake_node(block.clone(), -1);
+        match verify_tx_out_proof_locally(&node, &proof(&block)) {
+            Err(Error::MerkleProofStaleBlock(hash)) => assert_eq!(hash, block.bitcoin_hash()),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        // A block whose hash doesn't meet its target.
+        block.header.nonce += 1;
+        let node = fake_node(block.clone(), 1);
+        match verify_tx_out_proof_locally(&node, &proof(&block)) {
+            Err(Error::InvalidProofOfWork(hash)) => assert_eq!(hash, block.bitcoin_hash()),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
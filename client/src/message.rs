@@ -0,0 +1,199 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Signed messages as created by `signmessage`, with offline verification
+//! following BIP137.
+
+use std::{fmt, str};
+
+use base64;
+use bitcoin;
+use bitcoin::secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use bitcoin::secp256k1::{self, Secp256k1};
+use bitcoin::util::misc::signed_msg_hash;
+use bitcoin::{Address, PublicKey};
+
+use client::Result;
+use error::Error;
+
+/// A recoverable signature over a message, as used by `signmessage` and
+/// `verifymessage`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MessageSignature {
+    /// The signature, from which the public key can be recovered.
+    pub signature: RecoverableSignature,
+    /// Whether the signing key is compressed.
+    pub compressed: bool,
+}
+
+impl MessageSignature {
+    /// Parse the 65 byte serialization of a signature.
+    ///
+    /// Next to the headers used by Bitcoin Core, the BIP137 headers for
+    /// segwit addresses are accepted; those imply a compressed key.
+    pub fn from_slice(bytes: &[u8]) -> Result<MessageSignature> {
+        if bytes.len() != 65 || bytes[0] < 27 || bytes[0] > 42 {
+            return Err(Error::InvalidMessageSignature);
+        }
+        let flag = bytes[0] - 27;
+        let recid = RecoveryId::from_i32((flag & 3) as i32)?;
+        Ok(MessageSignature {
+            signature: RecoverableSignature::from_compact(&bytes[1..], recid)?,
+            compressed: flag >= 4,
+        })
+    }
+
+    /// Serialize the signature with the header byte used by Bitcoin Core.
+    pub fn serialize(&self) -> [u8; 65] {
+        let (recid, compact) = self.signature.serialize_compact();
+        let mut bytes = [0u8; 65];
+        let compressed_flag = if self.compressed {
+            4
+        } else {
+            0
+        };
+        bytes[0] = 27 + compressed_flag + recid.to_i32() as u8;
+        bytes[1..].copy_from_slice(&compact);
+        bytes
+    }
+
+    /// Parse a base64 encoded signature as returned by `signmessage`.
+    pub fn from_base64(s: &str) -> Result<MessageSignature> {
+        let bytes = base64::decode(s).map_err(|_| Error::InvalidMessageSignature)?;
+        MessageSignature::from_slice(&bytes)
+    }
+
+    /// Encode the signature in base64 as expected by `verifymessage`.
+    pub fn to_base64(&self) -> String {
+        base64::encode(&self.serialize()[..])
+    }
+
+    /// Recover the public key that signed `message`.
+    ///
+    /// Any signature recovers to some key, so the key has to be compared
+    /// with the expected one.
+    pub fn recover_pubkey<C: secp256k1::Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        message: &str,
+    ) -> Result<PublicKey> {
+        let msg = secp256k1::Message::from_slice(&signed_msg_hash(message)[..])?;
+        Ok(PublicKey {
+            compressed: self.compressed,
+            key: secp.recover(&msg, &self.signature)?,
+        })
+    }
+}
+
+impl fmt::Display for MessageSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_base64())
+    }
+}
+
+impl str::FromStr for MessageSignature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<MessageSignature> {
+        MessageSignature::from_base64(s)
+    }
+}
+
+/// Check offline that `signature` is a signature over `message` by the key
+/// of `address`, like `verifymessage` does.
+///
+/// P2PKH addresses are checked like Bitcoin Core does. For compressed keys
+/// the P2WPKH and P2SH-P2WPKH addresses of the key are accepted as well, as
+/// per BIP137.
+pub fn verify_message(
+    address: &Address,
+    signature: &MessageSignature,
+    message: &str,
+) -> Result<bool> {
+    let secp = Secp256k1::verification_only();
+    let pubkey = signature.recover_pubkey(&secp, message)?;
+
+    let network = address.network;
+    if *address == Address::p2pkh(&pubkey, network) {
+        return Ok(true);
+    }
+    Ok(pubkey.compressed
+        && (*address == Address::p2wpkh(&pubkey, network)
+            || *address == Address::p2shwpkh(&pubkey, network)))
+}
+
+/// Sign `message` with `key` the way `signmessagewithprivkey` does.
+pub fn sign_message<C: secp256k1::Signing>(
+    secp: &Secp256k1<C>,
+    key: &bitcoin::PrivateKey,
+    message: &str,
+) -> Result<MessageSignature> {
+    let msg = secp256k1::Message::from_slice(&signed_msg_hash(message)[..])?;
+    Ok(MessageSignature {
+        signature: secp.sign_recoverable(&msg, &key.key),
+        compressed: key.compressed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_message_signature() {
+        let secp = Secp256k1::new();
+        let key =
+            bitcoin::PrivateKey::from_wif("cMceqPhHedrhbcR9eXgzmfWy7kRqLyAxMYwFT6ABDWsiwUp9Nsq9")
+                .unwrap();
+        let p2pkh = Address::from_str("mrcNu71ztWjAQA6ww9kHiW3zBWSQidHXTQ").unwrap();
+        let p2shwpkh = Address::from_str("2MvtZ4txAvbaWRW2gXRmmrcUpQfsqNgpfUm").unwrap();
+        let p2wpkh = Address::from_str("bcrt1q0xcqpzrky6eff2g52qdye53xkk9jxkvrl4xfg5").unwrap();
+        let other = Address::from_str("n31WD8pkfAjg2APV78GnbDTdZb1QonBi5D").unwrap();
+
+        let signature = sign_message(&secp, &key, "hello").unwrap();
+        assert_eq!(signature.recover_pubkey(&secp, "hello").unwrap(), key.public_key(&secp));
+        assert!(verify_message(&p2pkh, &signature, "hello").unwrap());
+        assert!(verify_message(&p2shwpkh, &signature, "hello").unwrap());
+        assert!(verify_message(&p2wpkh, &signature, "hello").unwrap());
+        assert!(!verify_message(&other, &signature, "hello").unwrap());
+        assert!(!verify_message(&p2pkh, &signature, "hello!").unwrap());
+
+        let encoded = signature.to_string();
+        assert_eq!(MessageSignature::from_str(&encoded).unwrap(), signature);
+        assert!(signature.serialize()[0] >= 31);
+
+        // BIP137 header for P2WPKH.
+        let mut bytes = signature.serialize();
+        bytes[0] += 8;
+        assert_eq!(MessageSignature::from_slice(&bytes).unwrap(), signature);
+
+        bytes[0] = 43;
+        assert!(MessageSignature::from_slice(&bytes).is_err());
+        assert!(MessageSignature::from_base64("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_message_signature_core_vector() {
+        // From Bitcoin Core's rpc_signmessage.py functional test.
+        let secp = Secp256k1::new();
+        let key =
+            bitcoin::PrivateKey::from_wif("cUeKHd5orzT3mz8P9pxyREHfsWtVfgsfDjiZZBcjUBAaGk1BTj7N")
+                .unwrap();
+        let address = Address::from_str("mpLQjfK79b7CCV4VMJWEWAj5Mpx8Up5zxB").unwrap();
+        let message = "This is just a test message";
+        let expected = "INbVnW4e6PeRmsv2Qgu8NuopvrVjkcxob+sX8OcZG0SALhWybUjzMLPdAsXI46YZGb0KQTRii+wWIQzRpG/U+S0=";
+
+        let signature = sign_message(&secp, &key, message).unwrap();
+        assert_eq!(signature.to_base64(), expected);
+        let parsed = MessageSignature::from_base64(expected).unwrap();
+        assert!(verify_message(&address, &parsed, message).unwrap());
+    }
+}
@@ -0,0 +1,368 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Instrumentation of RPC calls.
+//!
+//! A [Client](::Client) reports every call to its [Metrics] hook, if it has
+//! one. With the `tracing` feature every call also runs in a `tracing` span
+//! named `rpc` with the fields `method`, `request_size`, `queue_wait_us`,
+//! `response_size`, `latency_us` and `error_code`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use jsonrpc;
+use serde;
+use serde_json;
+#[cfg(feature = "tracing")]
+use tracing;
+
+use client::Result;
+use error::Error;
+
+/// A finished RPC call, as reported to [Metrics].
+#[derive(Clone, Copy, Debug)]
+pub struct CallInfo<'a> {
+    pub method: &'a str,
+    /// The time the call waited for the rate limit and a free slot, see
+    /// [Client::with_rate_limit](::Client::with_rate_limit).
+    pub queue_wait: Duration,
+    /// The time from sending the request to receiving the response.
+    pub latency: Duration,
+    /// The size of the JSON request in bytes.
+    pub request_size: usize,
+    /// The size of the JSON response in bytes, `None` if the response was
+    /// decoded while it was received or the client was created with
+    /// [Client::from_jsonrpc](::Client::from_jsonrpc).
+    pub response_size: Option<usize>,
+    /// The error of a failed call.
+    pub error: Option<&'a Error>,
+}
+
+impl<'a> CallInfo<'a> {
+    /// The code of the error returned by the node, if any.
+    pub fn error_code(&self) -> Option<i32> {
+        match self.error {
+            Some(&Error::JsonRpc(jsonrpc::Error::Rpc(ref e))) => Some(e.code),
+            _ => None,
+        }
+    }
+}
+
+/// A hook that is told about every RPC call a client makes.
+pub trait Metrics: Send + Sync {
+    fn record_call(&self, call: &CallInfo);
+}
+
+/// Run the call `f` in a span and report it to `metrics`.
+///
+/// `f` is told whether to measure the size of the response, which is only
+/// needed when there is a hook or the span is enabled.
+pub(crate) fn instrument<T, R, F>(
+    metrics: Option<&dyn Metrics>,
+    method: &str,
+    queue_wait: Duration,
+    request: &R,
+    f: F,
+) -> Result<T>
+where
+    R: serde::Serialize,
+    F: FnOnce(bool) -> (Result<T>, Option<usize>),
+{
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!(
+        "rpc",
+        method,
+        request_size = tracing::field::Empty,
+        queue_wait_us = queue_wait.as_micros() as u64,
+        response_size = tracing::field::Empty,
+        latency_us = tracing::field::Empty,
+        error_code = tracing::field::Empty,
+    );
+    #[cfg(feature = "tracing")]
+    let traced = !span.is_disabled();
+    #[cfg(not(feature = "tracing"))]
+    let traced = false;
+    if metrics.is_none() && !traced {
+        return f(false).0;
+    }
+
+    let request_size = serde_json::to_vec(request).map(|r| r.len()).unwrap_or(0);
+    #[cfg(feature = "tracing")]
+    span.record("request_size", request_size);
+    #[cfg(feature = "tracing")]
+    let _enter = span.enter();
+
+    let start = Instant::now();
+    let (result, response_size) = f(true);
+    let info = CallInfo {
+        method,
+        queue_wait,
+        latency: start.elapsed(),
+        request_size,
+        response_size,
+        error: result.as_ref().err(),
+    };
+
+    #[cfg(feature = "tracing")]
+    {
+        if let Some(size) = info.response_size {
+            span.record("response_size", size);
+        }
+        span.record("latency_us", info.latency.as_micros() as u64);
+        if let Some(code) = info.error_code() {
+            span.record("error_code", code);
+        }
+    }
+    if let Some(metrics) = metrics {
+        metrics.record_call(&info);
+    }
+    result
+}
+
+/// Collects call counts, errors and histograms of latency and queue wait
+/// time per method and renders them in the Prometheus text format.
+pub struct PrometheusMetrics {
+    /// The upper bounds of the buckets in seconds.
+    buckets: Vec<f64>,
+    methods: Mutex<BTreeMap<String, MethodStats>>,
+}
+
+#[derive(Default)]
+struct MethodStats {
+    calls: u64,
+    /// The number of failed calls per error code, `None` for errors that
+    /// didn't come from the node.
+    errors: BTreeMap<Option<i32>, u64>,
+    latency: Histogram,
+    queue_wait: Histogram,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// The number of observations per bucket, not cumulative.
+    buckets: Vec<u64>,
+    sum: f64,
+}
+
+/// Escape a label value of the Prometheus text format.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+impl Histogram {
+    fn observe(&mut self, bounds: &[f64], duration: Duration) {
+        let seconds = duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9;
+        self.buckets.resize(bounds.len(), 0);
+        if let Some(bucket) = bounds.iter().position(|&bound| seconds <= bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += seconds;
+    }
+
+    fn render(&self, out: &mut String, name: &str, method: &str, bounds: &[f64], count: u64) {
+        let mut cumulative = 0;
+        for (bound, n) in bounds.iter().zip(&self.buckets) {
+            cumulative += n;
+            writeln!(
+                out,
+                "{}_bucket{{method=\"{}\",le=\"{}\"}} {}",
+                name, method, bound, cumulative
+            )
+            .unwrap();
+        }
+        writeln!(out, "{}_bucket{{method=\"{}\",le=\"+Inf\"}} {}", name, method, count).unwrap();
+        writeln!(out, "{}_sum{{method=\"{}\"}} {}", name, method, self.sum).unwrap();
+        writeln!(out, "{}_count{{method=\"{}\"}} {}", name, method, count).unwrap();
+    }
+}
+
+impl PrometheusMetrics {
+    /// Create metrics with buckets from 1 millisecond to 10 seconds.
+    pub fn new() -> PrometheusMetrics {
+        PrometheusMetrics::with_buckets(vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0])
+    }
+
+    /// Create metrics with the given bucket bounds in seconds.
+    pub fn with_buckets(mut buckets: Vec<f64>) -> PrometheusMetrics {
+        buckets.sort_by(|a, b| a.partial_cmp(b).expect("bucket bounds are numbers"));
+        PrometheusMetrics {
+            buckets,
+            methods: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Render the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let methods = self.methods.lock().unwrap();
+        let mut out = String::new();
+
+        out.push_str("# HELP bitcoind_rpc_calls_total RPC calls by method.\n");
+        out.push_str("# TYPE bitcoind_rpc_calls_total counter\n");
+        for (method, stats) in methods.iter() {
+            let method = escape_label(method);
+            writeln!(out, "bitcoind_rpc_calls_total{{method=\"{}\"}} {}", method, stats.calls)
+                .unwrap();
+        }
+
+        out.push_str(
+            "# HELP bitcoind_rpc_errors_total Failed RPC calls by method and error code.\n",
+        );
+        out.push_str("# TYPE bitcoind_rpc_errors_total counter\n");
+        for (method, stats) in methods.iter() {
+            let method = escape_label(method);
+            for (code, count) in &stats.errors {
+                let code = code.map_or_else(|| "other".to_owned(), |c| c.to_string());
+                writeln!(
+                    out,
+                    "bitcoind_rpc_errors_total{{method=\"{}\",code=\"{}\"}} {}",
+                    method, code, count
+                )
+                .unwrap();
+            }
+        }
+
+        out.push_str("# HELP bitcoind_rpc_latency_seconds RPC call latency by method.\n");
+        out.push_str("# TYPE bitcoind_rpc_latency_seconds histogram\n");
+        for (method, stats) in methods.iter() {
+            let name = "bitcoind_rpc_latency_seconds";
+            stats.latency.render(&mut out, name, &escape_label(method), &self.buckets, stats.calls);
+        }
+
+        out.push_str(
+            "# HELP bitcoind_rpc_queue_wait_seconds Time RPC calls waited for the rate limit.\n",
+        );
+        out.push_str("# TYPE bitcoind_rpc_queue_wait_seconds histogram\n");
+        for (method, stats) in methods.iter() {
+            let name = "bitcoind_rpc_queue_wait_seconds";
+            stats.queue_wait.render(
+                &mut out,
+                name,
+                &escape_label(method),
+                &self.buckets,
+                stats.calls,
+            );
+        }
+        out
+    }
+}
+
+impl Default for PrometheusMetrics {
+    fn default() -> PrometheusMetrics {
+        PrometheusMetrics::new()
+    }
+}
+
+impl Metrics for PrometheusMetrics {
+    fn record_call(&self, call: &CallInfo) {
+        let mut methods = self.methods.lock().unwrap();
+        let stats = methods.entry(call.method.to_owned()).or_default();
+        stats.calls += 1;
+        if call.error.is_some() {
+            *stats.errors.entry(call.error_code()).or_insert(0) += 1;
+        }
+        stats.latency.observe(&self.buckets, call.latency);
+        stats.queue_wait.observe(&self.buckets, call.queue_wait);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::{Auth, Client, RpcApi};
+    use std::sync::Arc;
+    use testutil::serve;
+
+    fn call<'a>(method: &'a str, millis: u64, error: Option<&'a Error>) -> CallInfo<'a> {
+        CallInfo {
+            method,
+            queue_wait: Duration::from_millis(0),
+            latency: Duration::from_millis(millis),
+            request_size: 10,
+            response_size: Some(20),
+            error,
+        }
+    }
+
+    #[test]
+    fn test_prometheus_metrics() {
+        let metrics = PrometheusMetrics::with_buckets(vec![0.1, 0.01]);
+        let not_found = Error::JsonRpc(jsonrpc::Error::Rpc(jsonrpc::error::RpcError {
+            code: -5,
+            message: "Block not found".into(),
+            data: None,
+        }));
+        metrics.record_call(&call("getblock", 5, None));
+        metrics.record_call(&call("getblock", 50, Some(&not_found)));
+        metrics.record_call(&call("getblock", 500, Some(&Error::UnexpectedStructure)));
+        metrics.record_call(&call("getblockcount", 1, None));
+
+        let rendered = metrics.render();
+        let expected = [
+            "bitcoind_rpc_calls_total{method=\"getblock\"} 3",
+            "bitcoind_rpc_calls_total{method=\"getblockcount\"} 1",
+            "bitcoind_rpc_errors_total{method=\"getblock\",code=\"other\"} 1",
+            "bitcoind_rpc_errors_total{method=\"getblock\",code=\"-5\"} 1",
+            "bitcoind_rpc_latency_seconds_bucket{method=\"getblock\",le=\"0.01\"} 1",
+            "bitcoind_rpc_latency_seconds_bucket{method=\"getblock\",le=\"0.1\"} 2",
+            "bitcoind_rpc_latency_seconds_bucket{method=\"getblock\",le=\"+Inf\"} 3",
+            "bitcoind_rpc_latency_seconds_sum{method=\"getblockcount\"} 0.001",
+            "bitcoind_rpc_latency_seconds_count{method=\"getblockcount\"} 1",
+        ];
+        for line in expected.iter() {
+            assert!(rendered.lines().any(|l| l == *line), "missing {} in\n{}", line, rendered);
+        }
+
+        metrics.record_call(&call("my\"call\\\n", 1, None));
+        let rendered = metrics.render();
+        let line = "bitcoind_rpc_calls_total{method=\"my\\\"call\\\\\\n\"} 1";
+        assert!(rendered.lines().any(|l| l == line), "missing {} in\n{}", line, rendered);
+    }
+
+    #[test]
+    fn test_client_metrics() {
+        let metrics = Arc::new(PrometheusMetrics::new());
+        let client = Client::new("http://127.0.0.1:1/".into(), Auth::None)
+            .unwrap()
+            .with_metrics(metrics.clone())
+            .with_rate_limit(50.0, 1);
+        assert!(client.get_block_count().is_err());
+        assert!(client.get_block(&Default::default()).is_err());
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("bitcoind_rpc_calls_total{method=\"getblockcount\"} 1\n"));
+        // The second call waited about 20ms for the rate limit.
+        let wait = "bitcoind_rpc_queue_wait_seconds_bucket{method=\"getblock\",le=\"0.005\"} 0\n";
+        assert!(rendered.contains(wait));
+        assert!(
+            rendered.contains("bitcoind_rpc_errors_total{method=\"getblock\",code=\"other\"} 1\n")
+        );
+    }
+
+    /// Keeps the response sizes of the calls.
+    struct ResponseSizes(Mutex<Vec<Option<usize>>>);
+
+    impl Metrics for ResponseSizes {
+        fn record_call(&self, call: &CallInfo) {
+            self.0.lock().unwrap().push(call.response_size);
+        }
+    }
+
+    #[test]
+    fn test_response_size() {
+        let url = serve(|request| serde_json::json!({ "result": 5, "id": request["id"] }));
+        let sizes = Arc::new(ResponseSizes(Mutex::new(Vec::new())));
+        let client = Client::new(url, Auth::None).unwrap().with_metrics(sizes.clone());
+        assert_eq!(client.get_block_count().unwrap(), 5);
+        assert_eq!(*sizes.0.lock().unwrap(), vec![Some(r#"{"id":1,"result":5}"#.len())]);
+    }
+}
@@ -0,0 +1,300 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Helpers to assemble and mine blocks from a `getblocktemplate` result.
+
+use std::collections::HashMap;
+
+use bitcoin;
+use bitcoin::blockdata::opcodes;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::hashes::Hash;
+use bitcoin::util::hash::BitcoinHash;
+use bitcoin::{Amount, Block, BlockHeader, OutPoint, Script, Transaction, TxIn, TxOut};
+
+use client::{Result, RpcApi};
+use error::Error;
+use json;
+
+/// The magic bytes that prefix the witness commitment in the coinbase output.
+const WITNESS_COMMITMENT_HEADER: [u8; 4] = [0xaa, 0x21, 0xa9, 0xed];
+
+/// Assemble a block from `template` that includes all the template
+/// transactions and pays the subsidy and their fees to `payout`.
+///
+/// The returned block has a valid merkle root and witness commitment, but
+/// its proof of work still has to be solved, see [solve_block].
+pub fn block_from_template(
+    template: &json::GetBlockTemplateResult,
+    payout: &Script,
+) -> Result<Block> {
+    let mut txdata = Vec::with_capacity(template.transactions.len());
+    for tx in &template.transactions {
+        txdata.push(tx.transaction()?);
+    }
+    block_from_template_with_txs(template, payout, txdata)
+}
+
+/// Assemble a block from `template` that includes the given transactions
+/// instead of the ones from the template.
+///
+/// The coinbase pays the block subsidy and the fees of those of `txdata` that
+/// are also part of the template; the fees of other transactions are unknown
+/// and thus forfeited. The caller is responsible for the transactions being
+/// valid and in a valid order.
+pub fn block_from_template_with_txs(
+    template: &json::GetBlockTemplateResult,
+    payout: &Script,
+    txdata: Vec<Transaction>,
+) -> Result<Block> {
+    let fees: HashMap<bitcoin::Txid, Amount> =
+        template.transactions.iter().map(|tx| (tx.txid, tx.fee)).collect();
+    let template_fees = fees.values().fold(Amount::ZERO, |acc, fee| acc + *fee);
+    let subsidy = template.coinbase_value.checked_sub(template_fees).unwrap_or(Amount::ZERO);
+    let included_fees = txdata
+        .iter()
+        .filter_map(|tx| fees.get(&tx.txid()))
+        .fold(Amount::ZERO, |acc, fee| acc + *fee);
+
+    let bits = match template.bits.len() {
+        4 => {
+            let b = &template.bits;
+            (b[0] as u32) << 24 | (b[1] as u32) << 16 | (b[2] as u32) << 8 | b[3] as u32
+        }
+        _ => return Err(Error::UnexpectedStructure),
+    };
+
+    let mut coinbase = coinbase_transaction(template.height, payout, subsidy + included_fees);
+    let mut block = Block {
+        header: BlockHeader {
+            version: template.version,
+            prev_blockhash: template.previous_block_hash,
+            merkle_root: Default::default(),
+            time: template.current_time as u32,
+            bits,
+            nonce: 0,
+        },
+        txdata: Vec::with_capacity(txdata.len() + 1),
+    };
+
+    let has_witness = txdata.iter().any(|tx| tx.input.iter().any(|i| !i.witness.is_empty()));
+    if has_witness || template.default_witness_commitment.is_some() {
+        // The witness reserved value is all zeroes and the coinbase's own wtxid
+        // is replaced by zeroes in the witness root, so the commitment can be
+        // computed before the coinbase is final.
+        let reserved_value = vec![0u8; 32];
+        coinbase.input[0].witness = vec![reserved_value.clone()];
+        block.txdata.push(coinbase);
+        block.txdata.extend(txdata);
+        let commitment = Block::compute_witness_commitment(&block.witness_root(), &reserved_value);
+        let mut script = WITNESS_COMMITMENT_HEADER.to_vec();
+        script.extend_from_slice(&commitment.into_inner());
+        block.txdata[0].output.push(TxOut {
+            value: 0,
+            script_pubkey: Builder::new()
+                .push_opcode(opcodes::all::OP_RETURN)
+                .push_slice(&script)
+                .into_script(),
+        });
+    } else {
+        block.txdata.push(coinbase);
+        block.txdata.extend(txdata);
+    }
+
+    block.header.merkle_root = block.merkle_root();
+    Ok(block)
+}
+
+/// Create a coinbase transaction for a block at `height` paying `value` to
+/// `payout`.
+///
+/// The scriptSig starts with the BIP34 height push.
+pub fn coinbase_transaction(height: u64, payout: &Script, value: Amount) -> Transaction {
+    Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Builder::new()
+                .push_int(height as i64)
+                .push_opcode(opcodes::OP_FALSE)
+                .into_script(),
+            sequence: 0xffffffff,
+            witness: vec![],
+        }],
+        output: vec![TxOut {
+            value: value.as_sat(),
+            script_pubkey: payout.clone(),
+        }],
+    }
+}
+
+/// Grind the nonce of the block header until its proof of work is valid.
+///
+/// Returns `false` if the nonce space was exhausted without finding a valid
+/// proof of work, in which case the time or the coinbase have to be changed
+/// before trying again.
+pub fn solve_block(header: &mut BlockHeader) -> bool {
+    let target = header.target();
+    loop {
+        if header.validate_pow(&target).is_ok() {
+            return true;
+        }
+        if header.nonce == u32::MAX {
+            return false;
+        }
+        header.nonce += 1;
+    }
+}
+
+/// Build, mine and submit a block on top of the current tip.
+///
+/// When `txdata` is `None`, the transactions from the node's template are
+/// included. Otherwise the block contains exactly the given transactions.
+/// Nonces are ground locally, so this is only practical on regtest.
+///
+/// Returns the block as it was accepted by the node.
+pub fn mine_regtest<C: RpcApi>(
+    rpc: &C,
+    payout: &Script,
+    txdata: Option<Vec<Transaction>>,
+) -> Result<Block> {
+    let template = rpc.get_block_template(
+        json::GetBlockTemplateModes::Template,
+        &[json::GetBlockTemplateRules::Segwit],
+        &[],
+    )?;
+    let mut block = match txdata {
+        Some(txdata) => block_from_template_with_txs(&template, payout, txdata)?,
+        None => block_from_template(&template, payout)?,
+    };
+    while !solve_block(&mut block.header) {
+        block.header.time += 1;
+        block.header.nonce = 0;
+    }
+    debug!("mined block {} at height {}", block.header.bitcoin_hash(), template.height);
+    rpc.submit_block(&block)?;
+    Ok(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::hex::FromHex;
+    use serde_json;
+
+    fn template(
+        transactions: &str,
+        coinbase_value: u64,
+        witness_commitment: bool,
+    ) -> json::GetBlockTemplateResult {
+        let commitment = if witness_commitment {
+            r#""default_witness_commitment": "6a24aa21a9ede2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf9","#
+        } else {
+            ""
+        };
+        let json = format!(
+            r#"
+            {{
+              "capabilities": ["proposal"],
+              "version": 536870912,
+              "rules": ["csv", "!segwit"],
+              "vbavailable": {{}},
+              "vbrequired": 0,
+              "previousblockhash": "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+              "transactions": {},
+              "coinbaseaux": {{}},
+              "coinbasevalue": {},
+              "longpollid": "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e22060",
+              "target": "7fffff0000000000000000000000000000000000000000000000000000000000",
+              "mintime": 1296688603,
+              "mutable": ["time", "transactions", "prevblock"],
+              "noncerange": "00000000ffffffff",
+              "sigoplimit": 80000,
+              "sizelimit": 4000000,
+              "weightlimit": 4000000,
+              "curtime": 1588000000,
+              "bits": "207fffff",
+              {}
+              "height": 1
+            }}
+            "#,
+            transactions, coinbase_value, commitment
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn payout() -> Script {
+        Script::from(Vec::<u8>::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap())
+    }
+
+    #[test]
+    fn test_block_from_empty_template() {
+        let template = template("[]", 5000000000, false);
+        let mut block = block_from_template(&template, &payout()).unwrap();
+
+        assert_eq!(block.txdata.len(), 1);
+        assert!(block.txdata[0].is_coin_base());
+        assert_eq!(block.txdata[0].output.len(), 1);
+        assert_eq!(block.txdata[0].output[0].value, 5000000000);
+        assert_eq!(
+            block.txdata[0].input[0].script_sig.as_bytes()[0],
+            opcodes::all::OP_PUSHNUM_1.into_u8()
+        );
+        assert_eq!(block.header.bits, 0x207fffff);
+        assert!(block.check_merkle_root());
+
+        assert!(solve_block(&mut block.header));
+        assert!(block.header.validate_pow(&block.header.target()).is_ok());
+    }
+
+    #[test]
+    fn test_block_from_template_witness_commitment() {
+        let template = template("[]", 5000000000, true);
+        let block = block_from_template(&template, &payout()).unwrap();
+
+        let coinbase = &block.txdata[0];
+        assert_eq!(coinbase.output.len(), 2);
+        assert_eq!(coinbase.input[0].witness, vec![vec![0u8; 32]]);
+        assert_eq!(
+            Some(&coinbase.output[1].script_pubkey),
+            template.default_witness_commitment.as_ref()
+        );
+        assert!(block.check_witness_commitment());
+        assert!(block.check_merkle_root());
+    }
+
+    #[test]
+    fn test_block_from_template_with_txs() {
+        let raw_tx = "0200000001586bd02815cf5faabfec986a4e50d25dbee089bd2758621e61c5fab06c334af0000000006b483045022100e85425f6d7c589972ee061413bcf08dc8c8e589ce37b217535a42af924f0e4d602205c9ba9cb14ef15513c9d946fa1c4b797883e748e8c32171bdf6166583946e35c012103dae30a4d7870cd87b45dd53e6012f71318fdd059c1c2623b8cc73f8af287bb2dfeffffff021dc4260c010000001976a914f602e88b2b5901d8aab15ebe4a97cf92ec6e03b388ac00e1f505000000001976a914687ffeffe8cf4e4c038da46a9b1d37db385a472d88acfd211500";
+        let transactions = format!(
+            r#"[{{
+                "data": "{}",
+                "txid": "4a5b5266e1750488395ac15c0376c9d48abf45e4df620777fe8cff096f57aa91",
+                "hash": "4a5b5266e1750488395ac15c0376c9d48abf45e4df620777fe8cff096f57aa91",
+                "depends": [],
+                "fee": 10000,
+                "sigops": 4,
+                "weight": 904
+            }}]"#,
+            raw_tx
+        );
+        let template = template(&transactions, 5000010000, false);
+
+        let block = block_from_template(&template, &payout()).unwrap();
+        assert_eq!(block.txdata.len(), 2);
+        assert_eq!(block.txdata[0].output[0].value, 5000010000);
+        assert!(block.check_merkle_root());
+
+        let block = block_from_template_with_txs(&template, &payout(), vec![]).unwrap();
+        assert_eq!(block.txdata.len(), 1);
+        assert_eq!(block.txdata[0].output[0].value, 5000000000);
+    }
+}
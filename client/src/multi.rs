@@ -0,0 +1,426 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Failover between several nodes.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use bitcoin::consensus::encode::Decodable;
+use jsonrpc;
+use serde;
+use serde_json;
+
+use client::{Client, Result, RpcApi};
+use error::Error;
+
+/// The calls that are retried on another node when the node they were sent
+/// to failed, because they don't change state or can safely be repeated.
+///
+/// Wallet calls are not in here as wallets are not shared between nodes.
+/// Node calls can also go to a wallet URL, so this holds for those too.
+const FAILOVER: &[&str] = &[
+    "analyzepsbt",
+    "combinepsbt",
+    "converttopsbt",
+    "createmultisig",
+    "createpsbt",
+    "createrawtransaction",
+    "decodepsbt",
+    "decoderawtransaction",
+    "decodescript",
+    "deriveaddresses",
+    "estimatesmartfee",
+    "finalizepsbt",
+    "getbestblockhash",
+    "getblock",
+    "getblockchaininfo",
+    "getblockcount",
+    "getblockfilter",
+    "getblockhash",
+    "getblockheader",
+    "getblockstats",
+    "getblocktemplate",
+    "getchaintips",
+    "getchaintxstats",
+    "getconnectioncount",
+    "getdescriptorinfo",
+    "getdifficulty",
+    "getindexinfo",
+    "getmempoolancestors",
+    "getmempooldescendants",
+    "getmempoolentry",
+    "getmempoolinfo",
+    "getmininginfo",
+    "getnettotals",
+    "getnetworkhashps",
+    "getnetworkinfo",
+    "getpeerinfo",
+    "getrawmempool",
+    "getrawtransaction",
+    "gettxout",
+    "gettxoutproof",
+    "gettxoutsetinfo",
+    "joinpsbts",
+    "sendrawtransaction",
+    "testmempoolaccept",
+    "uptime",
+    "utxoupdatepsbt",
+    "validateaddress",
+    "verifymessage",
+    "verifytxoutproof",
+];
+
+/// The RPC error code of a node that is still starting up.
+const RPC_IN_WARMUP: i32 = -28;
+
+/// Which healthy node a [MultiClient] sends calls to first.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Preference {
+    /// The node with the most blocks, ties are broken by latency.
+    MostBlocks,
+    /// The node that answered the last health check fastest.
+    LowestLatency,
+}
+
+/// The state of a node as seen by a [MultiClient].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NodeHealth {
+    pub healthy: bool,
+    /// The number of blocks at the last health check.
+    pub blocks: u64,
+    /// The latency of the last health check.
+    pub latency: Duration,
+}
+
+/// An [RpcApi] over several nodes that routes around unhealthy ones.
+///
+/// The healthy nodes are checked with `getblockchaininfo` when the last check
+/// is older than the check interval. Calls go to the preferred healthy node
+/// first. If a node can't be reached, it is marked unhealthy and the call is
+/// retried on the next node, unhealthy nodes being the last resort. A node
+/// that answers a call is healthy again. Errors returned by a node are
+/// passed on as they would be the same on all nodes.
+///
+/// Unhealthy nodes are left out of the periodic check so that a hung node
+/// doesn't hold up calls; [MultiClient::check_health] checks all nodes and
+/// can be called from a thread of its own to bring them back.
+///
+/// Only calls that don't change state, or can safely be repeated, are routed
+/// and retried on another node, see [MultiClient::failover]. All other calls,
+/// including the wallet calls, always go to the wallet node, see
+/// [MultiClient::wallet_node], as wallets are not shared between nodes.
+pub struct MultiClient<C = Client> {
+    nodes: Vec<Node<C>>,
+    preference: Preference,
+    check_interval: Duration,
+    last_check: Mutex<Option<Instant>>,
+    failover: HashSet<String>,
+    wallet_node: usize,
+}
+
+struct Node<C> {
+    rpc: C,
+    health: Mutex<NodeHealth>,
+}
+
+impl<C: RpcApi> MultiClient<C> {
+    /// Create a client over `nodes`, which must not be empty.
+    ///
+    /// By default the node with the most blocks is preferred and nodes are
+    /// checked every 10 seconds.
+    pub fn new(nodes: Vec<C>) -> MultiClient<C> {
+        assert!(!nodes.is_empty(), "a MultiClient needs at least one node");
+        MultiClient {
+            nodes: nodes
+                .into_iter()
+                .map(|rpc| Node {
+                    rpc,
+                    health: Mutex::new(NodeHealth {
+                        healthy: true,
+                        blocks: 0,
+                        latency: Duration::from_secs(0),
+                    }),
+                })
+                .collect(),
+            preference: Preference::MostBlocks,
+            check_interval: Duration::from_secs(10),
+            last_check: Mutex::new(None),
+            failover: FAILOVER.iter().map(|m| m.to_string()).collect(),
+            wallet_node: 0,
+        }
+    }
+
+    pub fn preference(mut self, preference: Preference) -> MultiClient<C> {
+        self.preference = preference;
+        self
+    }
+
+    pub fn check_interval(mut self, interval: Duration) -> MultiClient<C> {
+        self.check_interval = interval;
+        self
+    }
+
+    /// Retry calls of `method` on another node when the node they were sent
+    /// to failed. Only do this for methods that can safely run twice.
+    pub fn failover(mut self, method: &str) -> MultiClient<C> {
+        self.failover.insert(method.to_owned());
+        self
+    }
+
+    /// Send the calls that don't fail over to the node at `index` of the
+    /// nodes given, whatever its health. The first node by default.
+    pub fn wallet_node(mut self, index: usize) -> MultiClient<C> {
+        assert!(index < self.nodes.len(), "the wallet node must be one of the nodes");
+        self.wallet_node = index;
+        self
+    }
+
+    /// The nodes, in the order they were given.
+    pub fn nodes(&self) -> Vec<&C> {
+        self.nodes.iter().map(|n| &n.rpc).collect()
+    }
+
+    /// The health of the nodes as of the last check, in the order they were
+    /// given.
+    pub fn health(&self) -> Vec<NodeHealth> {
+        self.nodes.iter().map(|n| *n.health.lock().unwrap()).collect()
+    }
+
+    /// Check the health of all nodes now.
+    pub fn check_health(&self) {
+        self.check_nodes(true);
+    }
+
+    /// Check the health of the nodes, only the healthy ones unless `all`.
+    fn check_nodes(&self, all: bool) {
+        *self.last_check.lock().unwrap() = Some(Instant::now());
+        for (i, node) in self.nodes.iter().enumerate() {
+            if !all && !node.health.lock().unwrap().healthy {
+                continue;
+            }
+            let start = Instant::now();
+            // Only `blocks` is needed, which is stable across versions.
+            let result: Result<serde_json::Value> = node.rpc.call("getblockchaininfo", &[]);
+            let mut health = node.health.lock().unwrap();
+            health.latency = start.elapsed();
+            match result {
+                Ok(info) => {
+                    health.healthy = true;
+                    health.blocks = info["blocks"].as_u64().unwrap_or(0);
+                }
+                Err(e) => {
+                    warn!("node {} failed its health check: {}", i, e);
+                    health.healthy = false;
+                }
+            }
+        }
+    }
+
+    /// The indices of the nodes in the order they should be tried.
+    fn route(&self) -> Vec<usize> {
+        let due = match *self.last_check.lock().unwrap() {
+            Some(checked) => checked.elapsed() >= self.check_interval,
+            None => true,
+        };
+        if due {
+            self.check_nodes(false);
+        }
+
+        let health = self.health();
+        let mut order: Vec<usize> = (0..self.nodes.len()).collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (&health[a], &health[b]);
+            let by_preference = match self.preference {
+                Preference::MostBlocks => {
+                    b.blocks.cmp(&a.blocks).then_with(|| a.latency.cmp(&b.latency))
+                }
+                Preference::LowestLatency => a.latency.cmp(&b.latency),
+            };
+            b.healthy.cmp(&a.healthy).then(by_preference)
+        });
+        order
+    }
+
+    fn call_with<T, F: Fn(&C) -> Result<T>>(&self, cmd: &str, call: F) -> Result<T> {
+        let failover = self.failover.contains(cmd);
+        let order = if failover {
+            self.route()
+        } else {
+            vec![self.wallet_node]
+        };
+        let mut last_error = None;
+        for i in order {
+            let node = &self.nodes[i];
+            let error = match call(&node.rpc) {
+                Err(e) => e,
+                Ok(value) => {
+                    node.health.lock().unwrap().healthy = true;
+                    return Ok(value);
+                }
+            };
+            if !is_node_failure(&error) {
+                return Err(error);
+            }
+            warn!("node {} failed on {}: {}", i, cmd, error);
+            node.health.lock().unwrap().healthy = false;
+            last_error = Some(error);
+        }
+        Err(last_error.expect("there is at least one node"))
+    }
+}
+
+/// Whether `error` means that the node couldn't serve the call, rather
+/// than that it refused it or that its answer couldn't be decoded.
+fn is_node_failure(error: &Error) -> bool {
+    match *error {
+        Error::JsonRpc(jsonrpc::Error::Rpc(ref e)) => e.code == RPC_IN_WARMUP,
+        Error::JsonRpc(jsonrpc::Error::Hyper(_)) | Error::Io(_) => true,
+        _ => false,
+    }
+}
+
+impl<C: RpcApi> RpcApi for MultiClient<C> {
+    fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T> {
+        self.call_with(cmd, |rpc| rpc.call(cmd, args))
+    }
+
+    fn call_batch<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[Vec<serde_json::Value>],
+    ) -> Result<Vec<T>> {
+        self.call_with(cmd, |rpc| rpc.call_batch(cmd, args))
+    }
+
+    fn call_hex<T: Decodable>(&self, cmd: &str, args: &[serde_json::Value]) -> Result<T> {
+        self.call_with(cmd, |rpc| rpc.call_hex(cmd, args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::Amount;
+    use std::io;
+    use testutil::{rpc_error, FakeNode};
+
+    /// A node with a wallet whose tip is at height `blocks`.
+    fn fake_node(blocks: u64) -> FakeNode {
+        let mut node = FakeNode::new(blocks + 1);
+        node.on("getnewaddress", |_| Ok("bcrt1q0xcqpzrky6eff2g52qdye53xkk9jxkvrl4xfg5".into()));
+        node.on("getbalance", |_| Ok(1.0.into()));
+        node
+    }
+
+    #[test]
+    fn test_multi_client() {
+        let rpc = MultiClient::new(vec![fake_node(10), fake_node(12), fake_node(11)])
+            .check_interval(Duration::from_secs(3600));
+        let nodes = rpc.nodes();
+
+        // The node with the most blocks is preferred.
+        assert_eq!(rpc.get_block_count().unwrap(), 12);
+        assert_eq!(nodes[1].take_calls(), vec!["getblockchaininfo", "getblockcount"]);
+        assert_eq!(rpc.health()[0].blocks, 10);
+
+        // Errors returned by a node are not retried.
+        nodes[2].take_calls();
+        assert!(rpc.get_block_hash(100).is_err());
+        assert_eq!(nodes[1].take_calls(), vec!["getblockhash"]);
+        assert!(nodes[2].take_calls().is_empty());
+
+        nodes[1].set_down(true);
+        assert_eq!(rpc.get_block_count().unwrap(), 11);
+        assert!(!rpc.health()[1].healthy);
+        assert_eq!(nodes[1].take_calls(), vec!["getblockcount"]);
+
+        // Unhealthy nodes are tried last.
+        assert_eq!(rpc.get_block_count().unwrap(), 11);
+        assert!(nodes[1].take_calls().is_empty());
+
+        // Wallet calls and other calls that don't fail over always go to the
+        // wallet node, whatever node is preferred.
+        nodes[0].take_calls();
+        nodes[2].take_calls();
+        assert_eq!(rpc.get_balance(None, None).unwrap(), Amount::ONE_BTC);
+        assert!(rpc.call::<serde_json::Value>("unknown", &[]).is_err());
+        assert_eq!(nodes[0].take_calls(), vec!["getbalance", "unknown"]);
+        assert!(nodes[2].take_calls().is_empty());
+
+        // They are not retried when the wallet node fails, and still go to it
+        // when it is unhealthy.
+        nodes[0].set_down(true);
+        assert!(rpc.get_new_address(None, None).is_err());
+        assert!(!rpc.health()[0].healthy);
+        assert!(nodes[2].take_calls().is_empty());
+        nodes[0].set_down(false);
+        assert!(rpc.get_new_address(None, None).is_ok());
+        assert!(rpc.health()[0].healthy);
+        assert_eq!(nodes[0].take_calls(), vec!["getnewaddress", "getnewaddress"]);
+
+        // All nodes down, the error of the last one is returned.
+        nodes[0].set_down(true);
+        nodes[2].set_down(true);
+        assert!(rpc.get_block_count().is_err());
+
+        nodes[1].set_down(false);
+        rpc.check_health();
+        assert_eq!(rpc.get_block_count().unwrap(), 12);
+    }
+
+    #[test]
+    fn test_multi_client_wallet_node() {
+        let rpc = MultiClient::new(vec![fake_node(10), fake_node(12)]).wallet_node(1);
+        let nodes = rpc.nodes();
+        assert!(rpc.get_new_address(None, None).is_ok());
+        assert!(nodes[0].take_calls().is_empty());
+        assert_eq!(nodes[1].take_calls(), vec!["getnewaddress"]);
+    }
+
+    #[test]
+    fn test_is_node_failure() {
+        let io = || io::Error::new(io::ErrorKind::ConnectionRefused, "connection refused");
+        assert!(is_node_failure(&Error::Io(io())));
+        assert!(is_node_failure(&Error::JsonRpc(jsonrpc::Error::Hyper(io().into()))));
+        assert!(is_node_failure(&rpc_error(RPC_IN_WARMUP, "Loading block index...")));
+        assert!(!is_node_failure(&rpc_error(-8, "Block height out of range")));
+        // A result that doesn't decode is the client's problem, not the node's.
+        let decode = serde_json::from_str::<u64>("\"x\"").unwrap_err();
+        assert!(!is_node_failure(&Error::JsonRpc(jsonrpc::Error::Json(decode))));
+    }
+
+    #[test]
+    fn test_multi_client_health_check() {
+        let rpc = MultiClient::new(vec![fake_node(10), fake_node(12)])
+            .check_interval(Duration::from_secs(0));
+        let nodes = rpc.nodes();
+        assert_eq!(rpc.get_block_count().unwrap(), 12);
+
+        // The periodic check leaves out unhealthy nodes.
+        nodes[1].set_down(true);
+        assert_eq!(rpc.get_block_count().unwrap(), 10);
+        nodes[1].take_calls();
+        assert_eq!(rpc.get_block_count().unwrap(), 10);
+        assert!(nodes[1].take_calls().is_empty());
+
+        // A node is healthy again once it answers a call.
+        nodes[1].set_down(false);
+        nodes[0].set_down(true);
+        assert_eq!(rpc.get_block_count().unwrap(), 12);
+        assert!(rpc.health()[1].healthy);
+        assert!(!rpc.health()[0].healthy);
+    }
+}
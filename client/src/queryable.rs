@@ -9,10 +9,14 @@
 //
 
 use bitcoin;
+use bitcoin::consensus::encode::Decodable;
+use bitcoin::hashes::hex::FromHex;
+use serde;
 use serde_json;
 
 use client::Result;
 use client::RpcApi;
+use json;
 
 /// A type that can be queried from Bitcoin Core.
 pub trait Queryable<C: RpcApi>: Sized {
@@ -20,6 +24,33 @@ pub trait Queryable<C: RpcApi>: Sized {
     type Id;
     /// Query the item using `rpc` and convert to `Self`.
     fn query(rpc: &C, id: &Self::Id) -> Result<Self>;
+
+    /// Query the items with the given ids, in order.
+    ///
+    /// Implementations that need a single call per item send them as one
+    /// batch, see [RpcApi::call_batch].
+    fn query_many(rpc: &C, ids: &[Self::Id]) -> Result<Vec<Self>> {
+        ids.iter().map(|id| Self::query(rpc, id)).collect()
+    }
+}
+
+/// The arguments of one call per id, the id followed by `rest`.
+fn batch_args<I: serde::Serialize>(
+    ids: &[I],
+    rest: &[serde_json::Value],
+) -> Result<Vec<Vec<serde_json::Value>>> {
+    ids.iter()
+        .map(|id| {
+            let mut args = vec![serde_json::to_value(id)?];
+            args.extend(rest.iter().cloned());
+            Ok(args)
+        })
+        .collect()
+}
+
+fn decode_hex<T: Decodable>(hex: &str) -> Result<T> {
+    let bytes: Vec<u8> = FromHex::from_hex(hex)?;
+    Ok(bitcoin::consensus::encode::deserialize(&bytes)?)
 }
 
 impl<C: RpcApi> Queryable<C> for bitcoin::blockdata::block::Block {
@@ -31,6 +62,11 @@ impl<C: RpcApi> Queryable<C> for bitcoin::blockdata::block::Block {
         let bytes: Vec<u8> = bitcoin::hashes::hex::FromHex::from_hex(&hex)?;
         Ok(bitcoin::consensus::encode::deserialize(&bytes)?)
     }
+
+    fn query_many(rpc: &C, ids: &[Self::Id]) -> Result<Vec<Self>> {
+        let hexes: Vec<String> = rpc.call_batch("getblock", &batch_args(ids, &[0.into()])?)?;
+        hexes.iter().map(|hex| decode_hex(hex)).collect()
+    }
 }
 
 impl<C: RpcApi> Queryable<C> for bitcoin::blockdata::transaction::Transaction {
@@ -42,6 +78,119 @@ impl<C: RpcApi> Queryable<C> for bitcoin::blockdata::transaction::Transaction {
         let bytes: Vec<u8> = bitcoin::hashes::hex::FromHex::from_hex(&hex)?;
         Ok(bitcoin::consensus::encode::deserialize(&bytes)?)
     }
+
+    fn query_many(rpc: &C, ids: &[Self::Id]) -> Result<Vec<Self>> {
+        let hexes: Vec<String> = rpc.call_batch("getrawtransaction", &batch_args(ids, &[])?)?;
+        hexes.iter().map(|hex| decode_hex(hex)).collect()
+    }
+}
+
+impl<C: RpcApi> Queryable<C> for bitcoin::BlockHeader {
+    type Id = bitcoin::BlockHash;
+
+    fn query(rpc: &C, id: &Self::Id) -> Result<Self> {
+        rpc.get_block_header_raw(id)
+    }
+
+    fn query_many(rpc: &C, ids: &[Self::Id]) -> Result<Vec<Self>> {
+        let hexes: Vec<String> =
+            rpc.call_batch("getblockheader", &batch_args(ids, &[false.into()])?)?;
+        hexes.iter().map(|hex| decode_hex(hex)).collect()
+    }
+}
+
+impl<C: RpcApi> Queryable<C> for json::GetBlockResult {
+    type Id = bitcoin::BlockHash;
+
+    fn query(rpc: &C, id: &Self::Id) -> Result<Self> {
+        rpc.get_block_info(id)
+    }
+
+    fn query_many(rpc: &C, ids: &[Self::Id]) -> Result<Vec<Self>> {
+        rpc.call_batch("getblock", &batch_args(ids, &[1.into()])?)
+    }
+}
+
+impl<C: RpcApi> Queryable<C> for json::GetBlockHeaderResult {
+    type Id = bitcoin::BlockHash;
+
+    fn query(rpc: &C, id: &Self::Id) -> Result<Self> {
+        rpc.get_block_header_verbose(id)
+    }
+
+    fn query_many(rpc: &C, ids: &[Self::Id]) -> Result<Vec<Self>> {
+        rpc.call_batch("getblockheader", &batch_args(ids, &[true.into()])?)
+    }
+}
+
+impl<C: RpcApi> Queryable<C> for json::GetRawTransactionResult {
+    type Id = bitcoin::Txid;
+
+    fn query(rpc: &C, id: &Self::Id) -> Result<Self> {
+        rpc.get_raw_transaction_verbose(id, None)
+    }
+
+    fn query_many(rpc: &C, ids: &[Self::Id]) -> Result<Vec<Self>> {
+        rpc.call_batch("getrawtransaction", &batch_args(ids, &[true.into()])?)
+    }
+}
+
+impl<C: RpcApi> Queryable<C> for json::GetTransactionResult {
+    type Id = bitcoin::Txid;
+
+    fn query(rpc: &C, id: &Self::Id) -> Result<Self> {
+        rpc.get_transaction(id, None)
+    }
+
+    fn query_many(rpc: &C, ids: &[Self::Id]) -> Result<Vec<Self>> {
+        rpc.call_batch("gettransaction", &batch_args(ids, &[])?)
+    }
+}
+
+impl<C: RpcApi> Queryable<C> for json::GetAddressInfoResult {
+    type Id = bitcoin::Address;
+
+    fn query(rpc: &C, id: &Self::Id) -> Result<Self> {
+        rpc.get_address_info(id)
+    }
+
+    fn query_many(rpc: &C, ids: &[Self::Id]) -> Result<Vec<Self>> {
+        rpc.call_batch("getaddressinfo", &batch_args(ids, &[])?)
+    }
+}
+
+/// A block of the active chain, queried by its height.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BlockAtHeight {
+    pub height: u64,
+    pub block: bitcoin::Block,
+}
+
+impl<C: RpcApi> Queryable<C> for BlockAtHeight {
+    type Id = u64;
+
+    fn query(rpc: &C, id: &Self::Id) -> Result<Self> {
+        let hash = rpc.get_block_hash(*id)?;
+        Ok(BlockAtHeight {
+            height: *id,
+            block: Queryable::query(rpc, &hash)?,
+        })
+    }
+
+    /// Needs two batches, one for the hashes and one for the blocks.
+    fn query_many(rpc: &C, ids: &[Self::Id]) -> Result<Vec<Self>> {
+        let hashes: Vec<bitcoin::BlockHash> =
+            rpc.call_batch("getblockhash", &batch_args(ids, &[])?)?;
+        let blocks: Vec<bitcoin::Block> = Queryable::query_many(rpc, &hashes)?;
+        Ok(ids
+            .iter()
+            .zip(blocks)
+            .map(|(&height, block)| BlockAtHeight {
+                height,
+                block,
+            })
+            .collect())
+    }
 }
 
 impl<C: RpcApi> Queryable<C> for Option<::json::GetTxOutResult> {
@@ -51,3 +200,32 @@ impl<C: RpcApi> Queryable<C> for Option<::json::GetTxOutResult> {
         rpc.get_tx_out(&id.txid, id.vout, Some(true))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::util::hash::BitcoinHash;
+    use bitcoin::Block;
+    use testutil::FakeNode;
+
+    #[test]
+    fn test_query_many() {
+        let rpc = FakeNode::new(10);
+        let block: BlockAtHeight = rpc.get_by_id(&4).unwrap();
+        assert_eq!(block.height, 4);
+        assert_eq!(block.block, rpc.blocks[4]);
+        assert_eq!(rpc.take_calls(), vec!["getblockhash", "getblock"]);
+
+        let blocks: Vec<BlockAtHeight> = rpc.get_by_ids(&[7, 2, 9]).unwrap();
+        assert_eq!(blocks.iter().map(|b| b.height).collect::<Vec<_>>(), vec![7, 2, 9]);
+        for b in &blocks {
+            assert_eq!(b.block, rpc.blocks[b.height as usize]);
+        }
+        assert_eq!(rpc.take_calls(), vec!["batch getblockhash x3", "batch getblock x3"]);
+
+        let hashes: Vec<_> = rpc.blocks.iter().map(|b| b.bitcoin_hash()).collect();
+        let all: Vec<Block> = rpc.get_by_ids(&hashes).unwrap();
+        assert_eq!(all, rpc.blocks);
+        assert!(rpc.get_by_ids::<Block>(&[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,389 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Recording RPC calls to JSONL fixture files and replaying them.
+//!
+//! Every line of a fixture file holds one call, for example
+//! `{"method":"getblockcount","params":[],"result":101}`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use jsonrpc;
+use serde;
+use serde_json;
+
+use client::{Result, RpcApi};
+use error::Error;
+use redact::RedactionTable;
+
+/// A recorded call with either its result or its error.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+struct RecordedCall {
+    method: String,
+    params: Vec<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<RecordedError>,
+}
+
+/// Errors returned by the node keep their code, other errors are replayed
+/// as [Error::ReturnedError] with their message.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+struct RecordedError {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code: Option<i32>,
+    message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<serde_json::Value>,
+}
+
+impl RecordedCall {
+    fn describe(&self) -> String {
+        describe_call(&self.method, &self.params)
+    }
+
+    fn replay<T: for<'a> serde::de::Deserialize<'a>>(&self) -> Result<T> {
+        match self.error {
+            Some(RecordedError {
+                code: Some(code),
+                ref message,
+                ref data,
+            }) => Err(Error::JsonRpc(jsonrpc::Error::Rpc(jsonrpc::error::RpcError {
+                code,
+                message: message.clone(),
+                data: data.clone(),
+            }))),
+            Some(ref e) => Err(Error::ReturnedError(e.message.clone())),
+            None => {
+                Ok(serde_json::from_value(self.result.clone().unwrap_or(serde_json::Value::Null))?)
+            }
+        }
+    }
+}
+
+fn describe_call(method: &str, params: &[serde_json::Value]) -> String {
+    format!("{}{}", method, serde_json::Value::from(params.to_vec()))
+}
+
+/// An [RpcApi] wrapper that writes every call with its result or error to a
+/// JSONL fixture, to be served by a [ReplayClient].
+///
+/// Calls that decode hex results are recorded with their hex string.
+/// Secrets are masked according to [RedactionTable::default], so calls with
+/// secrets can be replayed but their redacted results can't be parsed.
+pub struct RecordingClient<C, W> {
+    rpc: C,
+    out: Mutex<W>,
+    redactions: RedactionTable,
+}
+
+impl<C: RpcApi> RecordingClient<C, File> {
+    /// Record to the file at `path`, replacing it if it exists.
+    pub fn create<P: AsRef<Path>>(rpc: C, path: P) -> Result<RecordingClient<C, File>> {
+        Ok(RecordingClient::new(rpc, File::create(path)?))
+    }
+}
+
+impl<C: RpcApi, W: Write> RecordingClient<C, W> {
+    pub fn new(rpc: C, out: W) -> RecordingClient<C, W> {
+        RecordingClient {
+            rpc,
+            out: Mutex::new(out),
+            redactions: RedactionTable::default(),
+        }
+    }
+
+    /// Mask secrets according to `redactions`.
+    pub fn with_redactions(mut self, redactions: RedactionTable) -> RecordingClient<C, W> {
+        self.redactions = redactions;
+        self
+    }
+
+    /// The wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.rpc
+    }
+
+    pub fn into_inner(self) -> (C, W) {
+        (self.rpc, self.out.into_inner().unwrap())
+    }
+}
+
+impl<C: RpcApi, W: Write> RpcApi for RecordingClient<C, W> {
+    fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T> {
+        let result: Result<serde_json::Value> = self.rpc.call(cmd, args);
+        let mut recorded = RecordedCall {
+            method: cmd.to_owned(),
+            params: self.redactions.redact_params(cmd, args),
+            result: None,
+            error: None,
+        };
+        match result {
+            Ok(ref value) => {
+                recorded.result = Some(self.redactions.redact_result(cmd, value.clone()))
+            }
+            Err(Error::JsonRpc(jsonrpc::Error::Rpc(ref e))) => {
+                recorded.error = Some(RecordedError {
+                    code: Some(e.code),
+                    message: e.message.clone(),
+                    data: e.data.clone(),
+                })
+            }
+            Err(ref e) => {
+                recorded.error = Some(RecordedError {
+                    code: None,
+                    message: e.to_string(),
+                    data: None,
+                })
+            }
+        }
+
+        let mut line = serde_json::to_vec(&recorded)?;
+        line.push(b'\n');
+        {
+            let mut out = self.out.lock().unwrap();
+            out.write_all(&line)?;
+            out.flush()?;
+        }
+        Ok(serde_json::from_value(result?)?)
+    }
+}
+
+/// How a [ReplayClient] matches calls with the recording.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReplayMode {
+    /// Calls have to be made in the recorded order with the recorded
+    /// arguments.
+    Strict,
+    /// Calls are matched by method and arguments in any order. Calls that
+    /// were recorded several times are answered in the recorded order and
+    /// the last answer is repeated once they are used up.
+    Lenient,
+}
+
+/// Serves the calls recorded by a [RecordingClient] without a node.
+///
+/// Calls are matched after masking their secrets like the recording did, so
+/// both need the same [RedactionTable].
+pub struct ReplayClient {
+    mode: ReplayMode,
+    redactions: RedactionTable,
+    calls: Vec<RecordedCall>,
+    /// The next call in strict mode, or the number of calls used per
+    /// call description in lenient mode.
+    state: Mutex<(usize, HashMap<String, usize>)>,
+}
+
+impl ReplayClient {
+    /// Load the fixture file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P, mode: ReplayMode) -> Result<ReplayClient> {
+        ReplayClient::from_reader(BufReader::new(File::open(path)?), mode)
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R, mode: ReplayMode) -> Result<ReplayClient> {
+        let mut calls = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                calls.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(ReplayClient {
+            mode,
+            redactions: RedactionTable::default(),
+            calls,
+            state: Mutex::new((0, HashMap::new())),
+        })
+    }
+
+    /// Mask secrets according to `redactions` before matching calls.
+    pub fn with_redactions(mut self, redactions: RedactionTable) -> ReplayClient {
+        self.redactions = redactions;
+        self
+    }
+
+    /// The number of recorded calls that were not replayed yet.
+    pub fn remaining(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        match self.mode {
+            ReplayMode::Strict => self.calls.len() - state.0,
+            ReplayMode::Lenient => {
+                let mut used = state.1.clone();
+                self.calls
+                    .iter()
+                    .filter(|c| match used.get_mut(&c.describe()) {
+                        Some(ref mut n) if **n > 0 => {
+                            **n -= 1;
+                            false
+                        }
+                        _ => true,
+                    })
+                    .count()
+            }
+        }
+    }
+}
+
+impl RpcApi for ReplayClient {
+    fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T> {
+        let args = &self.redactions.redact_params(cmd, args)[..];
+        let call = describe_call(cmd, args);
+        let mut state = self.state.lock().unwrap();
+        match self.mode {
+            ReplayMode::Strict => {
+                let recorded = match self.calls.get(state.0) {
+                    Some(recorded) => recorded,
+                    None => {
+                        return Err(Error::NoRecordedResponse(format!("{} after the end", call)))
+                    }
+                };
+                if recorded.method != cmd || recorded.params[..] != *args {
+                    return Err(Error::NoRecordedResponse(format!(
+                        "{} instead of {}",
+                        call,
+                        recorded.describe()
+                    )));
+                }
+                state.0 += 1;
+                recorded.replay()
+            }
+            ReplayMode::Lenient => {
+                let matching: Vec<&RecordedCall> = self
+                    .calls
+                    .iter()
+                    .filter(|c| c.method == cmd && c.params[..] == *args)
+                    .collect();
+                let used = state.1.entry(call.clone()).or_insert(0);
+                let recorded = match matching.get(*used).or_else(|| matching.last()) {
+                    Some(recorded) => recorded,
+                    None => return Err(Error::NoRecordedResponse(call)),
+                };
+                *used = (*used + 1).min(matching.len());
+                recorded.replay()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redact::REDACTED;
+    use std::sync::Mutex;
+    use testutil::FakeNode;
+
+    /// A node whose block count grows with every call.
+    fn fake_node(count: u64) -> FakeNode {
+        let mut node = FakeNode::new(10);
+        let count = Mutex::new(count);
+        node.on("getblockcount", move |_| {
+            let mut count = count.lock().unwrap();
+            *count += 1;
+            Ok((*count).into())
+        });
+        node.on("dumpprivkey", |_| {
+            Ok("cVpF924EspNh8KjYsfhgY96mmxvT6DgdWiTYMtMjuM74hJaU5psW".into())
+        });
+        node.on("stop", |_| Err(Error::ReturnedError("unknown command stop".into())));
+        node
+    }
+
+    fn record() -> Vec<u8> {
+        let rpc = RecordingClient::new(fake_node(100), Vec::new());
+        assert_eq!(rpc.get_block_count().unwrap(), 101);
+        assert_eq!(rpc.get_block_count().unwrap(), 102);
+        let hash = rpc.get_block_hash(0).unwrap();
+        assert!(rpc.get_block_hash(1000).is_err());
+        assert!(rpc.call::<serde_json::Value>("stop", &[]).is_err());
+        assert_eq!(rpc.get_block_hash(0).unwrap(), hash);
+        rpc.into_inner().1
+    }
+
+    #[test]
+    fn test_replay_strict() {
+        let fixture = record();
+        assert_eq!(
+            fixture.split(|&b| b == b'\n').next().unwrap(),
+            &br#"{"method":"getblockcount","params":[],"result":101}"#[..]
+        );
+
+        let rpc = ReplayClient::from_reader(&fixture[..], ReplayMode::Strict).unwrap();
+        assert_eq!(rpc.remaining(), 6);
+        assert_eq!(rpc.get_block_count().unwrap(), 101);
+        match rpc.get_block_hash(0) {
+            Err(Error::NoRecordedResponse(_)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+        assert_eq!(rpc.get_block_count().unwrap(), 102);
+        let hash = rpc.get_block_hash(0).unwrap();
+        match rpc.get_block_hash(1000) {
+            Err(Error::JsonRpc(jsonrpc::Error::Rpc(ref e))) if e.code == -8 => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match rpc.call::<serde_json::Value>("stop", &[]) {
+            Err(Error::ReturnedError(ref s)) => assert!(s.contains("unknown command stop")),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        assert_eq!(rpc.get_block_hash(0).unwrap(), hash);
+        assert_eq!(rpc.remaining(), 0);
+        assert!(rpc.get_block_count().is_err());
+    }
+
+    #[test]
+    fn test_record_redacted() {
+        let mut redactions = RedactionTable::default();
+        redactions.redact("getblockhash", &[0], false);
+        let rpc =
+            RecordingClient::new(fake_node(0), Vec::new()).with_redactions(redactions.clone());
+        let args = ["mrcNu71ztWjAQA6ww9kHiW3zBWSQidHXTQ".into()];
+        assert!(rpc.call::<String>("dumpprivkey", &args).unwrap().starts_with("cVpF"));
+        let hash = rpc.get_block_hash(5).unwrap();
+        let fixture = rpc.into_inner().1;
+        let fixture = String::from_utf8(fixture).unwrap();
+        assert!(!fixture.contains("cVpF"));
+        assert!(fixture.contains(r#""params":["<redacted>"]"#));
+
+        let rpc = ReplayClient::from_reader(fixture.as_bytes(), ReplayMode::Strict)
+            .unwrap()
+            .with_redactions(redactions);
+        assert_eq!(rpc.call::<String>("dumpprivkey", &args).unwrap(), REDACTED);
+        assert_eq!(rpc.get_block_hash(7).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_replay_lenient() {
+        let rpc = ReplayClient::from_reader(&record()[..], ReplayMode::Lenient).unwrap();
+        assert!(rpc.get_block_hash(0).is_ok());
+        assert!(rpc.get_block_hash(1000).is_err());
+        assert_eq!(rpc.get_block_count().unwrap(), 101);
+        assert_eq!(rpc.remaining(), 3);
+        assert_eq!(rpc.get_block_count().unwrap(), 102);
+        assert_eq!(rpc.get_block_count().unwrap(), 102);
+        assert!(rpc.get_block_hash(0).is_ok());
+        assert_eq!(rpc.remaining(), 1);
+        match rpc.get_block_hash(1) {
+            Err(Error::NoRecordedResponse(ref s)) => assert_eq!(s, "getblockhash[1]"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Masking of secrets before calls are logged or recorded.
+
+use std::collections::HashMap;
+
+use jsonrpc;
+use serde_json;
+
+/// The value that replaces redacted params and results.
+pub const REDACTED: &str = "<redacted>";
+
+/// The params and results of RPC methods that contain secrets.
+///
+/// [RedactionTable::default] covers the private keys, seeds and passphrases
+/// of the Bitcoin Core API; other methods, for example those called through
+/// `RpcApi::call`, can be added with [RedactionTable::redact].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RedactionTable {
+    methods: HashMap<String, Redaction>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+struct Redaction {
+    /// The positions of the sensitive params.
+    params: Vec<usize>,
+    result: bool,
+}
+
+impl RedactionTable {
+    /// A table that doesn't redact anything.
+    pub fn empty() -> RedactionTable {
+        RedactionTable {
+            methods: HashMap::new(),
+        }
+    }
+
+    /// Redact the params at the positions `params` of `method`, and its
+    /// result if `result` is set. Extends any redaction already in the
+    /// table.
+    pub fn redact(&mut self, method: &str, params: &[usize], result: bool) -> &mut RedactionTable {
+        let redaction = self.methods.entry(method.to_owned()).or_default();
+        redaction.params.extend_from_slice(params);
+        redaction.result |= result;
+        self
+    }
+
+    /// Whether any param or the result of `method` is redacted.
+    pub fn is_sensitive(&self, method: &str) -> bool {
+        self.methods.contains_key(method)
+    }
+
+    /// A copy of `params` with the sensitive ones masked.
+    pub fn redact_params(
+        &self,
+        method: &str,
+        params: &[serde_json::Value],
+    ) -> Vec<serde_json::Value> {
+        let mut params = params.to_vec();
+        if let Some(redaction) = self.methods.get(method) {
+            for &i in &redaction.params {
+                if let Some(param) = params.get_mut(i) {
+                    *param = REDACTED.into();
+                }
+            }
+        }
+        params
+    }
+
+    /// `result`, or the mask if the result of `method` is sensitive.
+    pub fn redact_result(&self, method: &str, result: serde_json::Value) -> serde_json::Value {
+        match self.methods.get(method) {
+            Some(redaction) if redaction.result && !result.is_null() => REDACTED.into(),
+            _ => result,
+        }
+    }
+
+    /// The JSON of `request` with its sensitive params masked.
+    pub(crate) fn request_json(&self, request: &jsonrpc::Request) -> String {
+        let params = self.redact_params(request.method, request.params);
+        let request = jsonrpc::Request {
+            method: request.method,
+            params: &params,
+            id: request.id.clone(),
+            jsonrpc: request.jsonrpc,
+        };
+        serde_json::to_string(&request).unwrap()
+    }
+
+    /// The JSON of `response` to a `method` call with a sensitive result
+    /// masked.
+    pub(crate) fn response_json(&self, method: &str, response: &jsonrpc::Response) -> String {
+        match self.methods.get(method) {
+            Some(redaction) if redaction.result => {
+                let mut response = response.clone();
+                response.result = response.result.map(|r| self.redact_result(method, r));
+                serde_json::to_string(&response).unwrap()
+            }
+            _ => serde_json::to_string(response).unwrap(),
+        }
+    }
+}
+
+impl Default for RedactionTable {
+    fn default() -> RedactionTable {
+        let mut table = RedactionTable::empty();
+        table
+            .redact("createwallet", &[3], false)
+            .redact("dumpprivkey", &[], true)
+            .redact("encryptwallet", &[0], false)
+            .redact("importdescriptors", &[0], false)
+            .redact("importmulti", &[0], false)
+            .redact("importprivkey", &[0], false)
+            // The descriptors hold the private keys with `private=true`.
+            .redact("listdescriptors", &[], true)
+            .redact("sethdseed", &[1], false)
+            .redact("signmessagewithprivkey", &[0], false)
+            .redact("signrawtransaction", &[2], false)
+            .redact("signrawtransactionwithkey", &[1], false)
+            .redact("walletpassphrase", &[0], false)
+            .redact("walletpassphrasechange", &[0, 1], false);
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redaction_table() {
+        let mut table = RedactionTable::default();
+        let params = vec!["secret".into(), 60.into()];
+        assert_eq!(
+            table.redact_params("walletpassphrase", &params),
+            vec![serde_json::Value::from(REDACTED), 60.into()]
+        );
+        assert_eq!(table.redact_params("getblock", &params), params);
+        assert_eq!(
+            table.redact_result("dumpprivkey", "cKey".into()),
+            serde_json::Value::from(REDACTED)
+        );
+        assert_eq!(table.redact_result("getblockcount", 5.into()), serde_json::Value::from(5));
+
+        let sign_params = vec!["0200".into(), serde_json::json!([]), serde_json::json!(["cKey"])];
+        assert_eq!(table.redact_params("signrawtransaction", &sign_params)[2], REDACTED);
+        let descriptors = serde_json::json!({ "descriptors": [{ "desc": "wpkh(tprv)" }] });
+        assert_eq!(table.redact_result("listdescriptors", descriptors), REDACTED);
+
+        // Out of range positions are ignored.
+        assert_eq!(table.redact_params("walletpassphrasechange", &params[..1]).len(), 1);
+
+        assert!(!table.is_sensitive("mysecretcall"));
+        table.redact("mysecretcall", &[1], true);
+        assert_eq!(table.redact_params("mysecretcall", &params)[1], REDACTED);
+        assert_eq!(table.redact_result("mysecretcall", "x".into()), REDACTED);
+
+        let request = jsonrpc::Request {
+            method: "importprivkey",
+            params: &params,
+            id: 1.into(),
+            jsonrpc: Some("2.0"),
+        };
+        assert!(!table.request_json(&request).contains("secret"));
+    }
+}
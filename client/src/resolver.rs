@@ -0,0 +1,262 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Finding transactions on nodes without `-txindex`.
+
+use std::ops::Range;
+
+use bitcoin;
+use bitcoin::{BlockHash, Transaction, Txid};
+use jsonrpc;
+
+use client::{Result, RpcApi};
+use error::Error;
+
+/// The error code of an unknown transaction or block.
+const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+/// The error code of a wallet call when no wallet is loaded.
+const RPC_WALLET_NOT_FOUND: i32 = -18;
+/// The error code of a wallet call when several wallets are loaded.
+const RPC_WALLET_NOT_SPECIFIED: i32 = -19;
+/// The error code of a wallet call on a node without wallet support.
+const RPC_METHOD_NOT_FOUND: i32 = -32601;
+
+const NOT_FOUND: &[i32] = &[RPC_INVALID_ADDRESS_OR_KEY];
+const WALLET_NOT_FOUND: &[i32] = &[
+    RPC_INVALID_ADDRESS_OR_KEY,
+    RPC_WALLET_NOT_FOUND,
+    RPC_WALLET_NOT_SPECIFIED,
+    RPC_METHOD_NOT_FOUND,
+];
+
+/// Where a [TxResolver] found a transaction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TxSource {
+    /// Unconfirmed, in the mempool.
+    Mempool,
+    /// Confirmed in the given block, found through `-txindex`.
+    TxIndex(BlockHash),
+    /// In the wallet, confirmed in the given block if any.
+    Wallet(Option<BlockHash>),
+    /// In the block given as hint.
+    BlockHint(BlockHash),
+    /// In the block at `height` of the scanned range.
+    Scan {
+        height: u64,
+        block_hash: BlockHash,
+    },
+}
+
+/// A transaction and where it was found.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ResolvedTx {
+    pub transaction: Transaction,
+    pub source: TxSource,
+}
+
+/// Looks up transactions in all places a node keeps them.
+///
+/// The mempool (and the transaction index, if the node has one) is tried
+/// first, then the wallet, then the block given as hint and finally the
+/// blocks of the scan range, if one is set. The resolver only moves on when
+/// the node reports that it doesn't have the transaction, or that it has no
+/// wallet or no wallet support for the wallet step; all other errors are
+/// returned as is.
+pub struct TxResolver<'a, C: 'a> {
+    rpc: &'a C,
+    scan_range: Option<Range<u64>>,
+}
+
+impl<'a, C: RpcApi> TxResolver<'a, C> {
+    pub fn new(rpc: &'a C) -> TxResolver<'a, C> {
+        TxResolver {
+            rpc,
+            scan_range: None,
+        }
+    }
+
+    /// Scan the blocks at the heights in `range` as a last resort.
+    pub fn scan_range(mut self, range: Range<u64>) -> TxResolver<'a, C> {
+        self.scan_range = Some(range);
+        self
+    }
+
+    /// Find the transaction `txid`, using `block_hint` if the block it was
+    /// confirmed in is known.
+    pub fn resolve(&self, txid: &Txid, block_hint: Option<&BlockHash>) -> Result<ResolvedTx> {
+        let info = self.rpc.get_raw_transaction_verbose(txid, None);
+        if let Some(info) = not_found_as_none(info, NOT_FOUND)? {
+            return Ok(ResolvedTx {
+                transaction: bitcoin::consensus::encode::deserialize(&info.hex)?,
+                source: match info.blockhash {
+                    Some(hash) => TxSource::TxIndex(hash),
+                    None => TxSource::Mempool,
+                },
+            });
+        }
+
+        let wallet_tx = self.rpc.get_transaction(txid, Some(true));
+        if let Some(info) = not_found_as_none(wallet_tx, WALLET_NOT_FOUND)? {
+            return Ok(ResolvedTx {
+                transaction: info.transaction()?,
+                source: TxSource::Wallet(info.info.blockhash),
+            });
+        }
+
+        if let Some(hash) = block_hint {
+            let tx = self.rpc.get_raw_transaction(txid, Some(hash));
+            if let Some(tx) = not_found_as_none(tx, NOT_FOUND)? {
+                return Ok(ResolvedTx {
+                    transaction: tx,
+                    source: TxSource::BlockHint(*hash),
+                });
+            }
+        }
+
+        if let Some(ref range) = self.scan_range {
+            for height in range.clone() {
+                let block_hash = self.rpc.get_block_hash(height)?;
+                let block = self.rpc.get_block(&block_hash)?;
+                if let Some(tx) = block.txdata.into_iter().find(|tx| tx.txid() == *txid) {
+                    return Ok(ResolvedTx {
+                        transaction: tx,
+                        source: TxSource::Scan {
+                            height,
+                            block_hash,
+                        },
+                    });
+                }
+            }
+        }
+
+        Err(Error::TransactionNotFound(*txid))
+    }
+}
+
+/// Turn the errors with one of the `codes` returned by the node into `None`.
+fn not_found_as_none<T>(result: Result<T>, codes: &[i32]) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::JsonRpc(jsonrpc::Error::Rpc(ref e))) if codes.contains(&e.code) => {
+            debug!("transaction lookup failed: {}", e.message);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::consensus::encode::serialize;
+    use bitcoin::hashes::hex::ToHex;
+    use bitcoin::util::hash::BitcoinHash;
+    use serde_json;
+    use testutil::{rpc_error, FakeNode};
+
+    /// Let `node` have a wallet with the transactions `txs`.
+    fn set_wallet(node: &mut FakeNode, txs: Vec<(Transaction, Option<BlockHash>)>) {
+        node.on("gettransaction", move |args| {
+            let txid: Txid = serde_json::from_value(args[0].clone())?;
+            let &(ref tx, blockhash) = txs
+                .iter()
+                .find(|entry| entry.0.txid() == txid)
+                .ok_or_else(|| rpc_error(-5, "Invalid or non-wallet transaction id"))?;
+            Ok(serde_json::json!({
+                "confirmations": if blockhash.is_some() { 1 } else { 0 },
+                "blockhash": blockhash,
+                "txid": txid,
+                "time": 0, "timereceived": 0,
+                "bip125-replaceable": "no",
+                "amount": 0.0,
+                "details": [],
+                "hex": serialize(tx).to_hex(),
+            }))
+        });
+    }
+
+    #[test]
+    fn test_tx_resolver_without_wallet() {
+        // A node without wallet support has no "gettransaction".
+        let node = FakeNode::new(5);
+        let tx = node.blocks[3].txdata[0].clone();
+        let hash_3 = node.hash(3);
+
+        let resolved = TxResolver::new(&node).resolve(&tx.txid(), Some(&hash_3)).unwrap();
+        assert_eq!(resolved.source, TxSource::BlockHint(hash_3));
+        let resolved = TxResolver::new(&node).scan_range(1..5).resolve(&tx.txid(), None).unwrap();
+        assert_eq!(resolved.transaction, tx);
+        assert_eq!(
+            node.take_calls(),
+            vec![
+                "getrawtransaction",
+                "gettransaction",
+                "getrawtransaction",
+                "getrawtransaction",
+                "gettransaction",
+                "getblockhash",
+                "getblock",
+                "getblockhash",
+                "getblock",
+                "getblockhash",
+                "getblock",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tx_resolver() {
+        let mut node = FakeNode::new(5);
+        set_wallet(&mut node, Vec::new());
+        let confirmed = node.blocks[3].txdata[0].clone();
+        let hash_3 = node.blocks[3].bitcoin_hash();
+        let mut unconfirmed = confirmed.clone();
+        unconfirmed.lock_time = 1;
+        node.mempool.push(unconfirmed.clone());
+
+        let resolver = TxResolver::new(&node);
+        let resolved = resolver.resolve(&unconfirmed.txid(), None).unwrap();
+        assert_eq!(resolved.transaction, unconfirmed);
+        assert_eq!(resolved.source, TxSource::Mempool);
+
+        let resolved = resolver.resolve(&confirmed.txid(), Some(&hash_3)).unwrap();
+        assert_eq!(resolved.transaction, confirmed);
+        assert_eq!(resolved.source, TxSource::BlockHint(hash_3));
+
+        match resolver.resolve(&confirmed.txid(), None) {
+            Err(Error::TransactionNotFound(txid)) => assert_eq!(txid, confirmed.txid()),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        let resolved = TxResolver::new(&node).scan_range(1..5).resolve(&confirmed.txid(), None);
+        assert_eq!(
+            resolved.unwrap().source,
+            TxSource::Scan {
+                height: 3,
+                block_hash: hash_3,
+            }
+        );
+        assert!(TxResolver::new(&node).scan_range(0..3).resolve(&confirmed.txid(), None).is_err());
+
+        set_wallet(&mut node, vec![(confirmed.clone(), Some(hash_3))]);
+        let resolved = TxResolver::new(&node).resolve(&confirmed.txid(), None).unwrap();
+        assert_eq!(resolved.source, TxSource::Wallet(Some(hash_3)));
+
+        node.txindex = true;
+        let resolved = TxResolver::new(&node).resolve(&confirmed.txid(), None).unwrap();
+        assert_eq!(resolved.source, TxSource::TxIndex(hash_3));
+
+        // Other errors of the node are not taken as not found.
+        node.intercept(|_, _| Some(Err(rpc_error(-28, "Loading block index..."))));
+        match TxResolver::new(&node).resolve(&confirmed.txid(), None) {
+            Err(Error::JsonRpc(jsonrpc::Error::Rpc(ref e))) => assert_eq!(e.code, -28),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
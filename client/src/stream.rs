@@ -0,0 +1,367 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Streaming decoding of hex encoded JSON-RPC results.
+//!
+//! Results like raw blocks are decoded while they are read from the HTTP
+//! response instead of being buffered as JSON, string and bytes first.
+
+use std::io::{self, BufRead, BufReader, Read};
+
+use bitcoin;
+use bitcoin::consensus::encode::Decodable;
+use bitcoin::hashes::hex::FromHex;
+use hyper;
+use hyper::header::{Authorization, Basic, ContentType, Headers};
+use jsonrpc;
+use serde_json;
+
+use client::Result;
+use error::Error;
+
+/// An HTTP transport that hands out response bodies as streams.
+pub(crate) struct HttpStream {
+    url: String,
+    user: Option<String>,
+    pass: Option<String>,
+    client: hyper::Client,
+}
+
+impl HttpStream {
+    pub(crate) fn new(url: String, user: Option<String>, pass: Option<String>) -> HttpStream {
+        HttpStream {
+            url,
+            user,
+            pass,
+            client: hyper::Client::new(),
+        }
+    }
+
+    /// Post `body`, retrying once like jsonrpc does when a pooled connection
+    /// was closed by the node.
+    fn post(&self, body: &[u8]) -> Result<hyper::client::Response> {
+        let mut headers = Headers::new();
+        headers.set(ContentType::json());
+        if let Some(ref user) = self.user {
+            headers.set(Authorization(Basic {
+                username: user.clone(),
+                password: self.pass.clone(),
+            }));
+        }
+
+        // The status is ignored like jsonrpc does, errors are in the body.
+        let send = |headers| self.client.post(&self.url).headers(headers).body(body).send();
+        match send(headers.clone()) {
+            Ok(response) => Ok(response),
+            Err(hyper::Error::Io(ref e))
+                if e.kind() == io::ErrorKind::BrokenPipe
+                    || e.kind() == io::ErrorKind::ConnectionAborted =>
+            {
+                Ok(send(headers).map_err(jsonrpc::Error::Hyper)?)
+            }
+            Err(e) => Err(jsonrpc::Error::Hyper(e).into()),
+        }
+    }
+
+    /// Send a request and decode its hex encoded result as it arrives.
+    pub(crate) fn call_hex<T: Decodable>(&self, request: &jsonrpc::Request) -> Result<T> {
+        let response = self.post(&serde_json::to_vec(request)?)?;
+        decode_hex_result(response, &request.id)
+    }
+
+    /// Send a request, returns the response and its size in bytes.
+    pub(crate) fn send_request(
+        &self,
+        request: &jsonrpc::Request,
+    ) -> Result<(jsonrpc::Response, usize)> {
+        let mut reader = CountingReader::new(self.post(&serde_json::to_vec(request)?)?);
+        let response: jsonrpc::Response = serde_json::from_reader(&mut reader)?;
+        // Drain the response so the connection can be reused.
+        io::copy(&mut reader, &mut io::sink())?;
+        check_response(&response, &request.id)?;
+        Ok((response, reader.count))
+    }
+
+    /// Send a batch of requests, returns the response to each request, if
+    /// any, and the size of all responses in bytes.
+    pub(crate) fn send_batch(
+        &self,
+        requests: &[jsonrpc::Request],
+    ) -> Result<(Vec<Option<jsonrpc::Response>>, usize)> {
+        let mut reader = CountingReader::new(self.post(&serde_json::to_vec(requests)?)?);
+        let responses: Vec<jsonrpc::Response> = serde_json::from_reader(&mut reader)?;
+        io::copy(&mut reader, &mut io::sink())?;
+        if responses.len() > requests.len() {
+            return Err(jsonrpc::Error::WrongBatchResponseSize.into());
+        }
+
+        // Match the responses to the requests like jsonrpc does.
+        let mut matched: Vec<Option<jsonrpc::Response>> = requests.iter().map(|_| None).collect();
+        for response in responses {
+            let i = match requests.iter().position(|r| r.id == response.id) {
+                Some(i) => i,
+                None => return Err(jsonrpc::Error::WrongBatchResponseId(response.id).into()),
+            };
+            if matched[i].is_some() {
+                return Err(jsonrpc::Error::BatchDuplicateResponseId(response.id).into());
+            }
+            matched[i] = Some(response);
+        }
+        Ok((matched, reader.count))
+    }
+}
+
+/// Counts the bytes read through it.
+struct CountingReader<R> {
+    inner: R,
+    count: usize,
+}
+
+impl<R> CountingReader<R> {
+    fn new(inner: R) -> CountingReader<R> {
+        CountingReader {
+            inner,
+            count: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n;
+        Ok(n)
+    }
+}
+
+/// Check the version and id of a response like jsonrpc does.
+fn check_response(response: &jsonrpc::Response, id: &serde_json::Value) -> Result<()> {
+    if response.jsonrpc.is_some() && response.jsonrpc.as_deref() != Some("2.0") {
+        return Err(jsonrpc::Error::VersionMismatch.into());
+    }
+    if response.id != *id {
+        return Err(jsonrpc::Error::NonceMismatch.into());
+    }
+    Ok(())
+}
+
+/// Decode the hex encoded result of a JSON-RPC response to the request with
+/// the id `id` read from `reader`.
+///
+/// Bitcoin Core always puts the result first, which allows to decode it
+/// before the rest of the response is read. Other responses, including
+/// errors, are parsed as a whole.
+pub fn decode_hex_result<T: Decodable, R: Read>(reader: R, id: &serde_json::Value) -> Result<T> {
+    let mut reader = BufReader::new(reader);
+    let mut prefix = Vec::new();
+    if read_result_key(&mut reader, &mut prefix)? {
+        let mut hex = HexReader::new(&mut reader);
+        let value = T::consensus_decode(&mut hex)?;
+        hex.finish()?;
+        // The rest of the response holds the id and the version, reading it
+        // all also allows to reuse the connection.
+        let mut rest = b"{\"result\":null".to_vec();
+        reader.read_to_end(&mut rest)?;
+        let response: jsonrpc::Response = serde_json::from_slice(&rest)?;
+        check_response(&response, id)?;
+        return Ok(value);
+    }
+
+    let mut body = prefix;
+    reader.read_to_end(&mut body)?;
+    let response: jsonrpc::Response = serde_json::from_slice(&body)?;
+    check_response(&response, id)?;
+    let hex: String = response.into_result()?;
+    let bytes: Vec<u8> = FromHex::from_hex(&hex)?;
+    Ok(bitcoin::consensus::encode::deserialize(&bytes)?)
+}
+
+/// Read up to the opening quote of a string result, `{"result":"`.
+///
+/// Returns `false` if the response starts differently, all bytes read are
+/// kept in `prefix`.
+fn read_result_key<R: BufRead>(reader: &mut R, prefix: &mut Vec<u8>) -> Result<bool> {
+    for token in &[&b"{"[..], b"\"result\"", b":", b"\""] {
+        let mut byte = next_non_whitespace(reader, prefix)?;
+        for (i, expected) in token.iter().enumerate() {
+            if i > 0 {
+                byte = next_byte(reader, prefix)?;
+            }
+            if byte != Some(*expected) {
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn next_byte<R: BufRead>(reader: &mut R, prefix: &mut Vec<u8>) -> Result<Option<u8>> {
+    let mut byte = [0u8];
+    if reader.read(&mut byte)? == 0 {
+        return Ok(None);
+    }
+    prefix.push(byte[0]);
+    Ok(Some(byte[0]))
+}
+
+fn next_non_whitespace<R: BufRead>(reader: &mut R, prefix: &mut Vec<u8>) -> Result<Option<u8>> {
+    loop {
+        match next_byte(reader, prefix)? {
+            Some(b) if (b as char).is_ascii_whitespace() => continue,
+            b => return Ok(b),
+        }
+    }
+}
+
+/// Reads the hex characters of a JSON string as bytes, up to the closing
+/// quote.
+struct HexReader<R> {
+    inner: R,
+    high: Option<u8>,
+    done: bool,
+}
+
+impl<R: BufRead> HexReader<R> {
+    fn new(inner: R) -> HexReader<R> {
+        HexReader {
+            inner,
+            high: None,
+            done: false,
+        }
+    }
+
+    /// Check that the whole string was consumed.
+    fn finish(mut self) -> Result<()> {
+        if !self.done {
+            // Only the closing quote may be left.
+            let mut rest = [0u8];
+            if self.read(&mut rest)? != 0 {
+                return Err(Error::UnexpectedStructure);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invalid_hex() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid hex in result")
+}
+
+impl<R: BufRead> Read for HexReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < out.len() && !self.done {
+            let consumed = {
+                let buf = self.inner.fill_buf()?;
+                if buf.is_empty() {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                let mut i = 0;
+                while i < buf.len() && n < out.len() {
+                    let c = buf[i];
+                    i += 1;
+                    if c == b'"' {
+                        if self.high.is_some() {
+                            return Err(invalid_hex());
+                        }
+                        self.done = true;
+                        break;
+                    }
+                    let nibble = (c as char).to_digit(16).ok_or_else(invalid_hex)? as u8;
+                    match self.high.take() {
+                        Some(high) => {
+                            out[n] = high << 4 | nibble;
+                            n += 1;
+                        }
+                        None => self.high = Some(nibble),
+                    }
+                }
+                i
+            };
+            self.inner.consume(consumed);
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::hex::ToHex;
+    use bitcoin::Block;
+
+    fn block() -> Block {
+        bitcoin::blockdata::constants::genesis_block(bitcoin::Network::Bitcoin)
+    }
+
+    #[test]
+    fn test_decode_hex_result() {
+        let block = block();
+        let hex = bitcoin::consensus::encode::serialize(&block).to_hex();
+
+        let response = format!(r#"{{"result":"{}","error":null,"id":1}}"#, hex);
+        let decoded: Block = decode_hex_result(response.as_bytes(), &1.into()).unwrap();
+        assert_eq!(decoded, block);
+
+        let response = format!("{{ \"result\" :\n \"{}\" , \"error\": null, \"id\": 1}}", hex);
+        let decoded: Block = decode_hex_result(response.as_bytes(), &1.into()).unwrap();
+        assert_eq!(decoded, block);
+
+        // Other key orders are parsed as a whole.
+        let response = format!(r#"{{"id":1,"error":null,"result":"{}"}}"#, hex);
+        let decoded: Block = decode_hex_result(response.as_bytes(), &1.into()).unwrap();
+        assert_eq!(decoded, block);
+
+        let response = format!(r#"{{"result":"{}00","error":null,"id":1}}"#, hex);
+        match decode_hex_result::<Block, _>(response.as_bytes(), &1.into()) {
+            Err(Error::UnexpectedStructure) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let response = format!(r#"{{"result":"{}","error":null,"id":1}}"#, &hex[..hex.len() - 2]);
+        assert!(decode_hex_result::<Block, _>(response.as_bytes(), &1.into()).is_err());
+
+        let response = format!(r#"{{"result":"{}x","error":null,"id":1}}"#, hex);
+        assert!(decode_hex_result::<Block, _>(response.as_bytes(), &1.into()).is_err());
+    }
+
+    #[test]
+    fn test_decode_hex_result_checks_response() {
+        let hex = bitcoin::consensus::encode::serialize(&block()).to_hex();
+
+        let response = format!(r#"{{"result":"{}","error":null,"id":2}}"#, hex);
+        match decode_hex_result::<Block, _>(response.as_bytes(), &1.into()) {
+            Err(Error::JsonRpc(jsonrpc::Error::NonceMismatch)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+        let response = format!(r#"{{"id":2,"error":null,"result":"{}"}}"#, hex);
+        match decode_hex_result::<Block, _>(response.as_bytes(), &1.into()) {
+            Err(Error::JsonRpc(jsonrpc::Error::NonceMismatch)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let response = format!(r#"{{"result":"{}","error":null,"id":1,"jsonrpc":"1.0"}}"#, hex);
+        match decode_hex_result::<Block, _>(response.as_bytes(), &1.into()) {
+            Err(Error::JsonRpc(jsonrpc::Error::VersionMismatch)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+        let response = format!(r#"{{"result":"{}","error":null,"id":1,"jsonrpc":"2.0"}}"#, hex);
+        assert!(decode_hex_result::<Block, _>(response.as_bytes(), &1.into()).is_ok());
+    }
+
+    #[test]
+    fn test_decode_hex_result_error() {
+        let response = r#"{"result":null,"error":{"code":-5,"message":"Block not found"},"id":1}"#;
+        match decode_hex_result::<Block, _>(response.as_bytes(), &1.into()) {
+            Err(Error::JsonRpc(jsonrpc::Error::Rpc(ref e))) if e.code == -5 => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
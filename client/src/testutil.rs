@@ -0,0 +1,304 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! A fake node for the tests of the modules built on [RpcApi].
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::consensus::encode::serialize;
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::util::hash::BitcoinHash;
+use bitcoin::{Amount, Block, BlockHash, Network, Script, Transaction, Txid};
+use jsonrpc;
+use serde;
+use serde_json::{self, Value};
+
+use client::{Result, RpcApi};
+use error::Error;
+use mining::coinbase_transaction;
+
+/// Answers a call instead of the node, or returns `None` to let it answer.
+type Handler = dyn Fn(&str, &[Value]) -> Option<Result<Value>> + Send + Sync;
+
+/// A node with a chain of blocks and a mempool that records the calls it
+/// receives.
+///
+/// It answers the chain and mempool calls itself, other calls can be
+/// answered by handlers, see [FakeNode::on] and [FakeNode::intercept].
+pub(crate) struct FakeNode {
+    /// The blocks of the active chain.
+    pub blocks: Vec<Block>,
+    /// Tips of other branches, with their height and status.
+    pub forks: Vec<(BlockHash, u64, &'static str)>,
+    pub mempool: Vec<Transaction>,
+    /// Whether `getrawtransaction` finds confirmed transactions without a
+    /// block hash.
+    pub txindex: bool,
+    /// Fail all calls as if the node couldn't be reached.
+    down: AtomicBool,
+    calls: Mutex<Vec<String>>,
+    handlers: Vec<Box<Handler>>,
+}
+
+/// A chain of `count` blocks, each with a coinbase paying to `OP_TRUE`.
+pub(crate) fn chain(count: u64) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    for height in 0..count {
+        let mut block = genesis_block(Network::Regtest);
+        let script = Script::from(vec![0x51]);
+        block.txdata = vec![coinbase_transaction(height, &script, Amount::ONE_BTC)];
+        block.header.merkle_root = block.merkle_root();
+        if let Some(prev) = blocks.last() {
+            block.header.prev_blockhash = prev.bitcoin_hash();
+        }
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// An error returned by the node.
+pub(crate) fn rpc_error(code: i32, message: &str) -> Error {
+    Error::JsonRpc(jsonrpc::Error::Rpc(jsonrpc::error::RpcError {
+        code,
+        message: message.into(),
+        data: None,
+    }))
+}
+
+impl FakeNode {
+    /// A node with a chain of `count` blocks.
+    pub fn new(count: u64) -> FakeNode {
+        FakeNode::with_blocks(chain(count))
+    }
+
+    pub fn with_blocks(blocks: Vec<Block>) -> FakeNode {
+        FakeNode {
+            blocks,
+            forks: Vec::new(),
+            mempool: Vec::new(),
+            txindex: false,
+            down: AtomicBool::new(false),
+            calls: Mutex::new(Vec::new()),
+            handlers: Vec::new(),
+        }
+    }
+
+    /// Answer calls of `cmd` with `handler`, before earlier handlers.
+    pub fn on<F>(&mut self, cmd: &'static str, handler: F)
+    where
+        F: Fn(&[Value]) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.intercept(move |c, args| {
+            if c == cmd {
+                Some(handler(args))
+            } else {
+                None
+            }
+        });
+    }
+
+    /// Let `handler` answer any call, before earlier handlers.
+    pub fn intercept<F>(&mut self, handler: F)
+    where
+        F: Fn(&str, &[Value]) -> Option<Result<Value>> + Send + Sync + 'static,
+    {
+        self.handlers.push(Box::new(handler));
+    }
+
+    pub fn set_down(&self, down: bool) {
+        self.down.store(down, Ordering::SeqCst);
+    }
+
+    /// The calls received since the last time, batches are recorded as
+    /// `batch <method> x<size>`.
+    pub fn take_calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().drain(..).collect()
+    }
+
+    pub fn hash(&self, height: usize) -> BlockHash {
+        self.blocks[height].bitcoin_hash()
+    }
+
+    fn height(&self, hash: &Value) -> Result<usize> {
+        let hash: BlockHash = serde_json::from_value(hash.clone())?;
+        self.blocks
+            .iter()
+            .position(|b| b.bitcoin_hash() == hash)
+            .ok_or_else(|| rpc_error(-5, "Block not found"))
+    }
+
+    fn find_tx(&self, txid: &Txid, hash: Option<usize>) -> Option<(&Transaction, Option<usize>)> {
+        if hash.is_none() {
+            if let Some(tx) = self.mempool.iter().find(|tx| tx.txid() == *txid) {
+                return Some((tx, None));
+            }
+            if !self.txindex {
+                return None;
+            }
+        }
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter(|&(height, _)| hash.is_none() || hash == Some(height))
+            .flat_map(|(height, b)| b.txdata.iter().map(move |tx| (tx, Some(height))))
+            .find(|&(tx, _)| tx.txid() == *txid)
+    }
+
+    fn answer(&self, cmd: &str, args: &[Value]) -> Result<Value> {
+        if self.down.load(Ordering::SeqCst) {
+            return Err(
+                io::Error::new(io::ErrorKind::ConnectionRefused, "connection refused").into()
+            );
+        }
+        for handler in self.handlers.iter().rev() {
+            if let Some(result) = handler(cmd, args) {
+                return result;
+            }
+        }
+
+        let tip = self.blocks.len() - 1;
+        let arg = |i: usize| args.get(i).cloned().unwrap_or(Value::Null);
+        Ok(match cmd {
+            "getblockcount" => tip.into(),
+            "getbestblockhash" => serde_json::to_value(self.hash(tip))?,
+            "getblockchaininfo" => serde_json::json!({
+                "blocks": tip,
+                "bestblockhash": self.hash(tip),
+            }),
+            "getblockhash" => match arg(0).as_u64() {
+                Some(height) if height as usize <= tip => {
+                    serde_json::to_value(self.hash(height as usize))?
+                }
+                _ => return Err(rpc_error(-8, "Block height out of range")),
+            },
+            "getblock" if arg(1) == 0 => {
+                serialize(&self.blocks[self.height(&arg(0))?]).to_hex().into()
+            }
+            "getblockheader" => {
+                let height = self.height(&arg(0))?;
+                if arg(1) == false {
+                    serialize(&self.blocks[height].header).to_hex().into()
+                } else {
+                    serde_json::json!({
+                        "hash": self.hash(height),
+                        "height": height,
+                        "confirmations": self.blocks.len() - height,
+                    })
+                }
+            }
+            "getchaintips" => {
+                let mut tips = vec![serde_json::json!({
+                    "height": tip, "hash": self.hash(tip), "branchlen": 0, "status": "active",
+                })];
+                for &(hash, height, status) in &self.forks {
+                    tips.push(serde_json::json!({
+                        "height": height, "hash": hash, "branchlen": 1, "status": status,
+                    }));
+                }
+                tips.into()
+            }
+            "getrawmempool" => {
+                serde_json::to_value(self.mempool.iter().map(|tx| tx.txid()).collect::<Vec<_>>())?
+            }
+            "getrawtransaction" => {
+                let txid: Txid = serde_json::from_value(arg(0))?;
+                let hash = match arg(2) {
+                    Value::Null => None,
+                    hash => Some(self.height(&hash)?),
+                };
+                let (tx, height) = self
+                    .find_tx(&txid, hash)
+                    .ok_or_else(|| rpc_error(-5, "No such mempool or blockchain transaction"))?;
+                if arg(1) == false {
+                    serialize(tx).to_hex().into()
+                } else {
+                    serde_json::json!({
+                        "hex": serialize(tx).to_hex(),
+                        "txid": tx.txid(),
+                        "hash": tx.wtxid(),
+                        "size": 0, "vsize": 0, "version": 1, "locktime": 0,
+                        "vin": [], "vout": [],
+                        "blockhash": height.map(|h| self.hash(h)),
+                    })
+                }
+            }
+            _ => return Err(rpc_error(-32601, "Method not found")),
+        })
+    }
+}
+
+impl RpcApi for FakeNode {
+    fn call<T: for<'a> serde::de::Deserialize<'a>>(&self, cmd: &str, args: &[Value]) -> Result<T> {
+        self.calls.lock().unwrap().push(cmd.to_owned());
+        Ok(serde_json::from_value(self.answer(cmd, args)?)?)
+    }
+
+    fn call_batch<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[Vec<Value>],
+    ) -> Result<Vec<T>> {
+        self.calls.lock().unwrap().push(format!("batch {} x{}", cmd, args.len()));
+        args.iter().map(|args| Ok(serde_json::from_value(self.answer(cmd, args)?)?)).collect()
+    }
+}
+
+/// Serve JSON-RPC over HTTP on a local port, `respond` answers every request
+/// of a call or a batch. Returns the URL.
+pub(crate) fn serve<F>(respond: F) -> String
+where
+    F: Fn(&Value) -> Value + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let respond = Arc::new(respond);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let respond = respond.clone();
+            thread::spawn(move || handle_connection(stream.unwrap(), &*respond));
+        }
+    });
+    url
+}
+
+fn handle_connection<F: Fn(&Value) -> Value>(mut stream: TcpStream, respond: &F) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    loop {
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                return;
+            }
+            let line = line.trim_end().to_ascii_lowercase();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(len) = line.strip_prefix("content-length:") {
+                content_length = len.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body).unwrap();
+        let request: Value = serde_json::from_slice(&body).unwrap();
+        let response = match request {
+            Value::Array(ref requests) => requests.iter().map(respond).collect::<Vec<_>>().into(),
+            ref request => respond(request),
+        }
+        .to_string();
+        write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", response.len()).unwrap();
+        stream.write_all(response.as_bytes()).unwrap();
+    }
+}
@@ -0,0 +1,550 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Helpers built on top of the wallet RPCs.
+
+use std::io::BufRead;
+use std::ops::Deref;
+use std::str::{self, FromStr};
+
+use bitcoin;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::util::bip32;
+use bitcoin::{Address, PrivateKey, Script};
+
+use client::{Result, RpcApi};
+use error::Error;
+use json;
+
+/// The fields that identify an entry of `listtransactions`.
+type HistoryEntryId = (bitcoin::Txid, u32, json::GetTransactionResultDetailCategory);
+
+fn history_entry_id(entry: &json::ListTransactionResult) -> HistoryEntryId {
+    (entry.info.txid, entry.detail.vout, entry.detail.category.clone())
+}
+
+/// An iterator over the wallet transaction history, newest entries first.
+///
+/// The history is fetched lazily with `listtransactions`, `page_size` entries
+/// at a time. Transactions that arrive at the wallet during the iteration
+/// shift the pages; each page overlaps the previous one by one entry so the
+/// shift is detected and every entry is yielded exactly once. Entries that
+/// arrive after the iteration started are not yielded.
+pub struct WalletHistory<'a, C: 'a> {
+    rpc: &'a C,
+    label: Option<String>,
+    page_size: usize,
+    include_watchonly: Option<bool>,
+    /// The number of entries, counting from the newest, that were fetched.
+    skip: usize,
+    /// The oldest entry yielded so far.
+    last: Option<HistoryEntryId>,
+    /// The current page, ordered oldest to newest like Core returns it.
+    page: Vec<json::ListTransactionResult>,
+    done: bool,
+}
+
+impl<'a, C: RpcApi> WalletHistory<'a, C> {
+    /// Create an iterator over the history of transactions with the given
+    /// label, or all transactions if `label` is `None`.
+    ///
+    /// Panics if `page_size` is zero.
+    pub fn new(
+        rpc: &'a C,
+        label: Option<&str>,
+        page_size: usize,
+        include_watchonly: Option<bool>,
+    ) -> WalletHistory<'a, C> {
+        assert!(page_size > 0, "page size must be positive");
+        WalletHistory {
+            rpc,
+            label: label.map(|l| l.to_owned()),
+            page_size,
+            include_watchonly,
+            skip: 0,
+            last: None,
+            page: Vec::new(),
+            done: false,
+        }
+    }
+
+    fn fetch_page(&mut self) -> Result<()> {
+        // Re-fetch the last yielded entry to find out where to continue.
+        let overlap = if self.last.is_some() {
+            1
+        } else {
+            0
+        };
+        let count = self.page_size + overlap;
+        let skip = self.skip - overlap;
+        let mut page = self.rpc.list_transactions(
+            Some(self.label.as_deref().unwrap_or("*")),
+            Some(count),
+            Some(skip),
+            self.include_watchonly,
+        )?;
+        let full = page.len() == count;
+        self.skip = skip + page.len();
+
+        if let Some(ref last) = self.last {
+            match page.iter().rposition(|e| history_entry_id(e) == *last) {
+                // Drop the entries that were already yielded.
+                Some(i) => page.truncate(i),
+                // So many entries arrived that this page only has new ones.
+                None if full => page.clear(),
+                // The last entry disappeared from the wallet, so there is no
+                // way to tell where to continue; yield the rest of the page.
+                None => {}
+            }
+        }
+        if !full {
+            self.done = true;
+        }
+        self.page = page;
+        Ok(())
+    }
+}
+
+impl<'a, C: RpcApi> Iterator for WalletHistory<'a, C> {
+    type Item = Result<json::ListTransactionResult>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.page.pop() {
+                self.last = Some(history_entry_id(&entry));
+                return Some(Ok(entry));
+            }
+
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.fetch_page() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/// Keeps an encrypted wallet unlocked while it is alive.
+///
+/// The wallet is unlocked with `walletpassphrase` on creation and locked
+/// again with `walletlock` when the guard is dropped, so the decryption key
+/// does not stay in the node's memory longer than needed. The node also
+/// locks the wallet by itself once `timeout` expires.
+pub struct UnlockedWallet<'a, C: 'a + RpcApi> {
+    rpc: &'a C,
+    locked: bool,
+}
+
+impl<'a, C: RpcApi> UnlockedWallet<'a, C> {
+    /// Unlock the wallet for at most `timeout` seconds.
+    pub fn new(rpc: &'a C, passphrase: &str, timeout: u64) -> Result<UnlockedWallet<'a, C>> {
+        rpc.wallet_passphrase(passphrase, timeout)?;
+        Ok(UnlockedWallet {
+            rpc,
+            locked: false,
+        })
+    }
+
+    /// Lock the wallet, returning any error that occurs.
+    ///
+    /// Dropping the guard also locks the wallet, but can only log errors.
+    pub fn lock(mut self) -> Result<()> {
+        self.locked = true;
+        self.rpc.wallet_lock()
+    }
+}
+
+impl<'a, C: RpcApi> Deref for UnlockedWallet<'a, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.rpc
+    }
+}
+
+impl<'a, C: RpcApi> Drop for UnlockedWallet<'a, C> {
+    fn drop(&mut self) {
+        if !self.locked {
+            if let Err(e) = self.rpc.wallet_lock() {
+                warn!("failed to lock the wallet: {}", e);
+            }
+        }
+    }
+}
+
+/// The role of a key in a wallet dump.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum WalletDumpKeyKind {
+    /// A key of an address in the address book, with its label.
+    Label(String),
+    /// The current HD seed.
+    HdSeed,
+    /// An HD seed that was replaced by another one.
+    InactiveHdSeed,
+    /// A key in the keypool.
+    Reserve,
+    /// Any other key, like change keys.
+    Change,
+}
+
+/// A private key from a wallet dump.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct WalletDumpKey {
+    pub key: PrivateKey,
+    /// The creation time of the key as a unix timestamp.
+    pub timestamp: u64,
+    pub kind: WalletDumpKeyKind,
+    /// The addresses of the key.
+    pub addresses: Vec<Address>,
+    /// The derivation path, if the key was derived from the HD seed.
+    pub hd_key_path: Option<bip32::DerivationPath>,
+}
+
+impl WalletDumpKey {
+    /// The label of the key, if it has one.
+    pub fn label(&self) -> Option<&str> {
+        match self.kind {
+            WalletDumpKeyKind::Label(ref label) => Some(label),
+            _ => None,
+        }
+    }
+}
+
+/// A redeem script from a wallet dump.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct WalletDumpScript {
+    pub script: Script,
+    /// The creation time of the script as a unix timestamp, zero if unknown.
+    pub timestamp: u64,
+    /// The addresses of the script.
+    pub addresses: Vec<Address>,
+}
+
+/// The contents of a wallet dump file as written by `dumpwallet`.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct WalletDump {
+    /// The extended private master key of the HD wallet.
+    pub master_key: Option<bip32::ExtendedPrivKey>,
+    pub keys: Vec<WalletDumpKey>,
+    pub scripts: Vec<WalletDumpScript>,
+}
+
+/// Parse a wallet dump file as written by [RpcApi::dump_wallet].
+pub fn parse_wallet_dump<R: BufRead>(reader: R) -> Result<WalletDump> {
+    let mut dump = WalletDump::default();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parsed = if line.starts_with('#') {
+            parse_wallet_dump_comment(&mut dump, line)
+        } else {
+            parse_wallet_dump_entry(&mut dump, line)
+        };
+        if parsed.is_none() {
+            return Err(Error::InvalidWalletDump(i + 1));
+        }
+    }
+    Ok(dump)
+}
+
+fn parse_wallet_dump_comment(dump: &mut WalletDump, line: &str) -> Option<()> {
+    if let Some(key) = line.strip_prefix("# extended private masterkey: ") {
+        dump.master_key = Some(key.trim().parse().ok()?);
+    }
+    Some(())
+}
+
+/// Parse a key or script line of the form
+/// `<key> <time> <flags> # addr=<address>[,<address>] [hdkeypath=<path>]`.
+fn parse_wallet_dump_entry(dump: &mut WalletDump, line: &str) -> Option<()> {
+    let (data, comment) = match line.find(" # ") {
+        Some(i) => (&line[..i], &line[i + 3..]),
+        None => (line, ""),
+    };
+
+    let mut fields = data.split_whitespace();
+    let first = fields.next()?;
+    let timestamp = parse_timestamp(fields.next()?)?;
+    let mut kind = None;
+    let mut is_script = false;
+    for flag in fields {
+        match flag.split_once('=')? {
+            ("label", label) => kind = Some(WalletDumpKeyKind::Label(percent_decode(label)?)),
+            ("hdseed", _) | ("hdmaster", _) => kind = Some(WalletDumpKeyKind::HdSeed),
+            ("inactivehdseed", _) => kind = Some(WalletDumpKeyKind::InactiveHdSeed),
+            ("reserve", _) => kind = Some(WalletDumpKeyKind::Reserve),
+            ("change", _) => kind = Some(WalletDumpKeyKind::Change),
+            ("script", _) => is_script = true,
+            _ => {}
+        }
+    }
+
+    let mut addresses = Vec::new();
+    let mut hd_key_path = None;
+    for field in comment.split_whitespace() {
+        if let Some(addrs) = field.strip_prefix("addr=") {
+            for addr in addrs.split(',') {
+                addresses.push(Address::from_str(addr).ok()?);
+            }
+        } else if let Some(path) = field.strip_prefix("hdkeypath=") {
+            // Older versions use "s" for the seed itself.
+            if path != "s" {
+                hd_key_path = Some(bip32::DerivationPath::from_str(path).ok()?);
+            }
+        }
+    }
+
+    if is_script {
+        dump.scripts.push(WalletDumpScript {
+            script: Script::from(Vec::<u8>::from_hex(first).ok()?),
+            timestamp,
+            addresses,
+        });
+    } else {
+        dump.keys.push(WalletDumpKey {
+            key: PrivateKey::from_wif(first).ok()?,
+            timestamp,
+            kind: kind?,
+            addresses,
+            hd_key_path,
+        });
+    }
+    Some(())
+}
+
+/// Parse a `YYYY-MM-DDTHH:MM:SSZ` date into a unix timestamp; "0" is used
+/// for unknown dates.
+fn parse_timestamp(s: &str) -> Option<u64> {
+    if s == "0" {
+        return Some(0);
+    }
+    let b = s.as_bytes();
+    if b.len() != 20
+        || b[4] != b'-'
+        || b[7] != b'-'
+        || b[10] != b'T'
+        || b[13] != b':'
+        || b[16] != b':'
+        || b[19] != b'Z'
+    {
+        return None;
+    }
+    let num = |from: usize, to: usize| s[from..to].parse::<u64>().ok();
+    let (year, month, day) = (num(0, 4)?, num(5, 7)?, num(8, 10)?);
+    let (hour, min, sec) = (num(11, 13)?, num(14, 16)?, num(17, 19)?);
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let y = if month <= 2 {
+        year - 1
+    } else {
+        year
+    };
+    let era = y / 400;
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    Some(days * 86400 + hour * 3600 + min * 60 + sec)
+}
+
+/// Decode the `%XX` escapes Core uses for labels in wallet dumps.
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde;
+    use serde_json;
+    use std::cell::RefCell;
+
+    /// A fake wallet that lists `entries` (oldest first) and receives
+    /// `arrivals` of the `incoming` transactions after every page that is
+    /// requested.
+    struct FakeWallet {
+        entries: RefCell<Vec<serde_json::Value>>,
+        incoming: RefCell<Vec<serde_json::Value>>,
+        arrivals: usize,
+    }
+
+    impl FakeWallet {
+        fn new(entries: usize, incoming: usize, arrivals: usize) -> FakeWallet {
+            FakeWallet {
+                entries: RefCell::new((1..entries as u8 + 1).map(entry).collect()),
+                incoming: RefCell::new((100..100 + incoming as u8).map(entry).collect()),
+                arrivals,
+            }
+        }
+    }
+
+    impl RpcApi for FakeWallet {
+        fn call<T: for<'a> serde::de::Deserialize<'a>>(
+            &self,
+            cmd: &str,
+            args: &[serde_json::Value],
+        ) -> Result<T> {
+            assert_eq!(cmd, "listtransactions");
+            let count = args[1].as_u64().unwrap() as usize;
+            let skip = args[2].as_u64().unwrap() as usize;
+
+            let entries = self.entries.borrow().clone();
+            let end = entries.len().saturating_sub(skip);
+            let start = end.saturating_sub(count);
+            let page = entries[start..end].to_vec();
+
+            let mut incoming = self.incoming.borrow_mut();
+            let arrivals = incoming.len().min(self.arrivals);
+            self.entries.borrow_mut().extend(incoming.drain(..arrivals));
+            Ok(serde_json::from_value(serde_json::Value::Array(page))?)
+        }
+    }
+
+    fn entry(i: u8) -> serde_json::Value {
+        serde_json::json!({
+            "address": "mq3VuL2K63VKWkp8vvqRiJPre4h9awrHfA",
+            "category": "receive",
+            "amount": 1.0,
+            "vout": 0,
+            "confirmations": 1,
+            "txid": format!("{:064x}", i),
+            "time": 1534934745,
+            "timereceived": 1534934745,
+            "bip125-replaceable": "no"
+        })
+    }
+
+    fn txids<C: RpcApi>(history: WalletHistory<C>) -> Vec<u8> {
+        history.map(|e| e.unwrap().info.txid[0]).collect()
+    }
+
+    /// A fake node that records the commands it is called with.
+    #[derive(Default)]
+    struct Recorder {
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl RpcApi for Recorder {
+        fn call<T: for<'a> serde::de::Deserialize<'a>>(
+            &self,
+            cmd: &str,
+            _args: &[serde_json::Value],
+        ) -> Result<T> {
+            self.calls.borrow_mut().push(cmd.to_owned());
+            Ok(serde_json::from_value(serde_json::Value::Null)?)
+        }
+    }
+
+    #[test]
+    fn test_unlocked_wallet() {
+        let rpc = Recorder::default();
+        {
+            let unlocked = UnlockedWallet::new(&rpc, "secret", 60).unwrap();
+            assert_eq!(*unlocked.calls.borrow(), vec!["walletpassphrase"]);
+        }
+        assert_eq!(*rpc.calls.borrow(), vec!["walletpassphrase", "walletlock"]);
+
+        let rpc = Recorder::default();
+        UnlockedWallet::new(&rpc, "secret", 60).unwrap().lock().unwrap();
+        assert_eq!(*rpc.calls.borrow(), vec!["walletpassphrase", "walletlock"]);
+    }
+
+    #[test]
+    fn test_parse_wallet_dump() {
+        let file = "\
+# Wallet dump created by Bitcoin v0.20.0
+# * Created on 2020-05-01T12:00:00Z
+# * Best block at time of backup was 101 (0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206),
+#   mined on 2020-05-01T11:58:00Z
+
+# extended private masterkey: tprv8ZgxMBicQKsPdDdJFAqvG3mt4VqsVV125X4vsor5NxK366upt6qvovLQqaCi5SJiCE1aLkt3HtxsnTpzeGu27kPC5RUCr4h3oPBPYnAvhdE
+
+cMceqPhHedrhbcR9eXgzmfWy7kRqLyAxMYwFT6ABDWsiwUp9Nsq9 2020-05-01T12:00:00Z hdseed=1 # addr=mrcNu71ztWjAQA6ww9kHiW3zBWSQidHXTQ hdkeypath=s
+cMec2DGaTXkYJYfi7x3ZGjRXkeqmAvYAoWzMAcWj5fdLaqudWsNi 2020-05-01T12:00:00Z label=customer%2042%25 # addr=n31WD8pkfAjg2APV78GnbDTdZb1QonBi5D,2NG7GwqV3rBao6wh55MqTumV9JJocWT4RH2,bcrt1qa0qwuze2h85zw7nqpsj3ga0z9geyrgwpf2m8je hdkeypath=m/0'/0'/0'
+cMgZD2qsGReP1UvGbNQ7moL6PZFgzsuPFV3St8sGwpNxED4hqkEM 1970-01-01T00:00:01Z reserve=1 # addr=mmVEKH61BZbLbnVEmk9VmojreB4G4PmBPd hdkeypath=m/0'/1'/0'
+0014eb80ee0b2ab9e8a773c030944757b151ba2c8cc0 0 script=1 # addr=2NG7GwqV3rBao6wh55MqTumV9JJocWT4RH2
+
+# End of dump
+";
+        let dump = parse_wallet_dump(file.as_bytes()).unwrap();
+
+        assert_eq!(dump.master_key.unwrap().to_string(), "tprv8ZgxMBicQKsPdDdJFAqvG3mt4VqsVV125X4vsor5NxK366upt6qvovLQqaCi5SJiCE1aLkt3HtxsnTpzeGu27kPC5RUCr4h3oPBPYnAvhdE");
+        assert_eq!(dump.keys.len(), 3);
+
+        let seed = &dump.keys[0];
+        assert_eq!(seed.key.to_wif(), "cMceqPhHedrhbcR9eXgzmfWy7kRqLyAxMYwFT6ABDWsiwUp9Nsq9");
+        assert_eq!(seed.timestamp, 1588334400);
+        assert_eq!(seed.kind, WalletDumpKeyKind::HdSeed);
+        assert_eq!(seed.hd_key_path, None);
+
+        let labeled = &dump.keys[1];
+        assert_eq!(labeled.label(), Some("customer 42%"));
+        assert_eq!(labeled.addresses.len(), 3);
+        assert_eq!(
+            labeled.addresses[2].to_string(),
+            "bcrt1qa0qwuze2h85zw7nqpsj3ga0z9geyrgwpf2m8je"
+        );
+        assert_eq!(
+            labeled.hd_key_path,
+            Some(bip32::DerivationPath::from_str("m/0'/0'/0'").unwrap())
+        );
+
+        assert_eq!(dump.keys[2].kind, WalletDumpKeyKind::Reserve);
+        assert_eq!(dump.keys[2].timestamp, 1);
+
+        assert_eq!(dump.scripts.len(), 1);
+        assert_eq!(dump.scripts[0].timestamp, 0);
+        assert_eq!(dump.scripts[0].addresses[0].to_string(), "2NG7GwqV3rBao6wh55MqTumV9JJocWT4RH2");
+
+        match parse_wallet_dump("# header\nnot-a-key 2020-05-01T12:00:00Z change=1\n".as_bytes()) {
+            Err(Error::InvalidWalletDump(2)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_wallet_history() {
+        let wallet = FakeWallet::new(7, 0, 0);
+        assert_eq!(txids(WalletHistory::new(&wallet, None, 3, None)), vec![7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(txids(WalletHistory::new(&wallet, None, 7, None)), vec![7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(txids(WalletHistory::new(&wallet, None, 100, None)), vec![7, 6, 5, 4, 3, 2, 1]);
+
+        let wallet = FakeWallet::new(0, 0, 0);
+        assert!(txids(WalletHistory::new(&wallet, None, 3, None)).is_empty());
+    }
+
+    #[test]
+    fn test_wallet_history_insertions() {
+        for &(page_size, arrivals) in &[(3, 1), (1, 1), (3, 2), (2, 5), (3, 20)] {
+            let wallet = FakeWallet::new(7, 20, arrivals);
+            let history = WalletHistory::new(&wallet, None, page_size, None);
+            assert_eq!(txids(history), vec![7, 6, 5, 4, 3, 2, 1]);
+        }
+    }
+}
@@ -27,6 +27,7 @@ use std::str::FromStr;
 use std::collections::HashMap;
 
 use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::sha256d;
 use bitcoin::consensus::encode;
 use bitcoin::util::{bip158, bip32};
 use bitcoin::{Address, Amount, PrivateKey, PublicKey, Script, Transaction};
@@ -86,6 +87,38 @@ pub struct LoadWalletResult {
     pub warning: Option<String>,
 }
 
+/// Models the result of "dumpwallet"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct DumpWalletResult {
+    /// The absolute path of the dump file on the node
+    pub filename: String,
+}
+
+/// Models the result of "upgradewallet"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct UpgradeWalletResult {
+    /// The name of the wallet
+    pub wallet_name: String,
+    /// The version of the wallet before the upgrade
+    pub previous_version: u32,
+    /// The version of the wallet after the upgrade
+    pub current_version: u32,
+    /// A description of the result, if any
+    pub result: Option<String>,
+    /// The error message, if any
+    pub error: Option<String>,
+}
+
+/// Models the result of "rescanblockchain"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct RescanBlockchainResult {
+    /// The block height where the rescan started
+    pub start_height: u64,
+    /// The height of the last rescanned block, `None` if the rescan was
+    /// aborted
+    pub stop_height: Option<u64>,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetBlockResult {
@@ -113,11 +146,81 @@ pub struct GetBlockResult {
     pub nextblockhash: Option<bitcoin::BlockHash>,
 }
 
+/// Models the result of "getblock" with verbosity 2 or 3
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct GetBlockHeaderResult {
+pub struct GetBlockVerboseTxResult {
     pub hash: bitcoin::BlockHash,
     pub confirmations: u32,
+    pub size: usize,
+    pub strippedsize: Option<usize>,
+    pub weight: usize,
+    pub height: usize,
+    pub version: u32,
+    #[serde(default, with = "::serde_hex::opt")]
+    pub version_hex: Option<Vec<u8>>,
+    pub merkleroot: bitcoin::TxMerkleNode,
+    pub tx: Vec<GetBlockVerboseTxResultTx>,
+    pub time: usize,
+    pub mediantime: Option<usize>,
+    pub nonce: u32,
+    pub bits: String,
+    #[serde(deserialize_with = "deserialize_difficulty")]
+    pub difficulty: BigUint,
+    #[serde(with = "::serde_hex")]
+    pub chainwork: Vec<u8>,
+    pub n_tx: usize,
+    pub previousblockhash: Option<bitcoin::BlockHash>,
+    pub nextblockhash: Option<bitcoin::BlockHash>,
+}
+
+impl GetBlockVerboseTxResult {
+    /// The sum of the fees of all transactions in the block.
+    ///
+    /// Returns `None` if the node did not provide the fee of some non-coinbase
+    /// transaction, which happens when its undo data was pruned.
+    pub fn total_fee(&self) -> Option<Amount> {
+        self.tx.iter().filter(|tx| !tx.is_coinbase()).try_fold(Amount::ZERO, |acc, tx| {
+            tx.fee.map(|fee| acc + fee)
+        })
+    }
+}
+
+/// Models a transaction in the result of "getblock" with verbosity 2 or 3
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct GetBlockVerboseTxResultTx {
+    pub txid: bitcoin::Txid,
+    pub hash: bitcoin::Wtxid,
+    pub size: usize,
+    pub vsize: usize,
+    pub weight: usize,
+    pub version: u32,
+    pub locktime: u32,
+    pub vin: Vec<GetRawTransactionResultVin>,
+    pub vout: Vec<GetRawTransactionResultVout>,
+    #[serde(with = "::serde_hex")]
+    pub hex: Vec<u8>,
+    /// Not provided for the coinbase or if undo data is not available.
+    #[serde(default, with = "bitcoin::util::amount::serde::as_btc::opt")]
+    pub fee: Option<Amount>,
+}
+
+impl GetBlockVerboseTxResultTx {
+    pub fn is_coinbase(&self) -> bool {
+        self.vin.first().map(|vin| vin.is_coinbase()).unwrap_or(false)
+    }
+
+    pub fn transaction(&self) -> Result<Transaction, encode::Error> {
+        encode::deserialize(&self.hex)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetBlockHeaderResult {
+    pub hash: bitcoin::BlockHash,
+    /// -1 if the block is not on the active chain.
+    pub confirmations: i32,
     pub height: usize,
     pub version: u32,
     #[serde(default, with = "::serde_hex::opt")]
@@ -180,6 +283,9 @@ pub struct GetRawTransactionResultVin {
     /// Not provided for coinbase txs.
     #[serde(default, deserialize_with = "deserialize_hex_array_opt")]
     pub txinwitness: Option<Vec<Vec<u8>>>,
+    /// The output spent by this input, only provided by "getblock" with
+    /// verbosity 3.
+    pub prevout: Option<GetRawTransactionResultVinPrevout>,
 }
 
 impl GetRawTransactionResultVin {
@@ -191,6 +297,17 @@ impl GetRawTransactionResultVin {
     }
 }
 
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRawTransactionResultVinPrevout {
+    /// Whether the output was created by a coinbase transaction.
+    pub generated: bool,
+    pub height: u64,
+    #[serde(with = "bitcoin::util::amount::serde::as_btc")]
+    pub value: Amount,
+    pub script_pub_key: GetRawTransactionResultVoutScriptPubKey,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetRawTransactionResultVoutScriptPubKey {
@@ -201,6 +318,8 @@ pub struct GetRawTransactionResultVoutScriptPubKey {
     #[serde(rename = "type")]
     pub type_: Option<ScriptPubkeyType>,
     pub addresses: Option<Vec<Address>>,
+    /// Replaces [addresses] since Bitcoin Core v22.
+    pub address: Option<Address>,
 }
 
 impl GetRawTransactionResultVoutScriptPubKey {
@@ -239,6 +358,60 @@ pub struct GetRawTransactionResult {
     pub blocktime: Option<usize>,
 }
 
+/// Models the result of "decoderawtransaction"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct DecodeRawTransactionResult {
+    pub txid: bitcoin::Txid,
+    pub hash: bitcoin::Wtxid,
+    pub size: usize,
+    pub vsize: usize,
+    pub weight: usize,
+    pub version: u32,
+    pub locktime: u32,
+    pub vin: Vec<GetRawTransactionResultVin>,
+    pub vout: Vec<GetRawTransactionResultVout>,
+}
+
+/// Models the result of "decodescript"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodeScriptResult {
+    pub asm: String,
+    /// Only provided by some versions of Bitcoin Core.
+    #[serde(default, with = "::serde_hex::opt")]
+    pub hex: Option<Vec<u8>>,
+    #[serde(rename = "type")]
+    pub type_: Option<ScriptPubkeyType>,
+    pub req_sigs: Option<usize>,
+    pub addresses: Option<Vec<Address>>,
+    /// The P2SH address wrapping this script, not provided for P2SH scripts.
+    pub p2sh: Option<Address>,
+    /// The segwit version of this script, only provided if it can be wrapped.
+    pub segwit: Option<DecodeScriptResultSegwit>,
+}
+
+/// Models the "segwit" field of the result of "decodescript"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodeScriptResultSegwit {
+    pub asm: String,
+    #[serde(with = "::serde_hex")]
+    pub hex: Vec<u8>,
+    #[serde(rename = "type")]
+    pub type_: Option<ScriptPubkeyType>,
+    pub req_sigs: Option<usize>,
+    pub addresses: Option<Vec<Address>>,
+    /// The P2SH address wrapping the segwit script.
+    #[serde(rename = "p2sh-segwit")]
+    pub p2sh_segwit: Option<Address>,
+}
+
+impl DecodeScriptResultSegwit {
+    pub fn script(&self) -> Script {
+        Script::from(self.hex.clone())
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 pub struct GetBlockFilterResult {
     pub header: bitcoin::FilterHash,
@@ -260,6 +433,12 @@ impl GetBlockFilterResult {
             content: self.filter,
         }
     }
+
+    /// Check that the filter header commits to the filter and to the header
+    /// of the previous block's filter.
+    pub fn check_header(&self, previous_header: &bitcoin::FilterHash) -> bool {
+        self.to_filter().filter_id(previous_header) == self.header
+    }
 }
 
 impl GetRawTransactionResult {
@@ -283,7 +462,7 @@ pub enum Bip125Replaceable {
 }
 
 /// Enum to represent the BIP125 replaceable status for a transaction.
-#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum GetTransactionResultDetailCategory {
     Send,
@@ -347,6 +526,21 @@ pub struct ListTransactionResult {
     pub comment: Option<String>,
 }
 
+/// Models the result of "listsinceblock"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+pub struct ListSinceBlockResult {
+    /// The wallet transactions since the given block
+    pub transactions: Vec<ListTransactionResult>,
+    /// The transactions that were removed from the chain by a reorg, only
+    /// present if `include_removed` was set
+    #[serde(default)]
+    pub removed: Vec<ListTransactionResult>,
+    /// The hash of the block `target_confirmations - 1` from the best block
+    /// on the main chain
+    #[serde(rename = "lastblock")]
+    pub last_block: bitcoin::BlockHash,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetTxOutResult {
@@ -358,6 +552,73 @@ pub struct GetTxOutResult {
     pub coinbase: bool,
 }
 
+/// Models the result of "gettxoutsetinfo"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct GetTxOutSetInfoResult {
+    /// The current block height (index)
+    pub height: u64,
+    /// The hash of the block at the tip of the chain
+    #[serde(rename = "bestblock")]
+    pub best_block: bitcoin::BlockHash,
+    /// The number of transactions with unspent outputs
+    pub transactions: u64,
+    /// The number of unspent transaction outputs
+    #[serde(rename = "txouts")]
+    pub tx_outs: u64,
+    /// A meaningless metric for UTXO set size
+    pub bogosize: u64,
+    /// The serialized hash
+    #[serde(rename = "hash_serialized_2")]
+    pub hash_serialized: sha256d::Hash,
+    /// The estimated size of the chainstate on disk
+    pub disk_size: u64,
+    /// The total amount
+    #[serde(with = "bitcoin::util::amount::serde::as_btc")]
+    pub total_amount: Amount,
+}
+
+/// An unspent output found by "scantxoutset"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct ScanTxOutResultUnspent {
+    pub txid: bitcoin::Txid,
+    pub vout: u32,
+    #[serde(rename = "scriptPubKey")]
+    pub script_pub_key: Script,
+    /// The descriptor matching the output
+    #[serde(rename = "desc")]
+    pub descriptor: Option<String>,
+    #[serde(with = "bitcoin::util::amount::serde::as_btc")]
+    pub amount: Amount,
+    /// The height of the block containing the output
+    pub height: u64,
+}
+
+/// Models the result of "scantxoutset" with the "start" action
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct ScanTxOutResult {
+    /// Whether the scan was completed (Bitcoin Core v0.20+)
+    pub success: Option<bool>,
+    /// The number of unspent transaction outputs scanned
+    #[serde(rename = "txouts", alias = "searched_items")]
+    pub tx_outs: Option<u64>,
+    /// The current block height (index) (Bitcoin Core v0.20+)
+    pub height: Option<u64>,
+    /// The hash of the block at the tip of the chain (Bitcoin Core v0.20+)
+    #[serde(rename = "bestblock")]
+    pub best_block: Option<bitcoin::BlockHash>,
+    pub unspents: Vec<ScanTxOutResultUnspent>,
+    /// The total amount of all found unspent outputs
+    #[serde(with = "bitcoin::util::amount::serde::as_btc")]
+    pub total_amount: Amount,
+}
+
+/// Models the result of "scantxoutset" with the "status" action
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct ScanTxOutStatusResult {
+    /// The scan progress in percent
+    pub progress: u8,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListUnspentQueryOptions {
@@ -404,6 +665,39 @@ pub struct ListReceivedByAddressResult {
     pub txids: Vec<bitcoin::Txid>,
 }
 
+/// Models an entry of the result of "listreceivedbylabel"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct ListReceivedByLabelResult {
+    #[serde(rename = "involvesWatchonly", default)]
+    pub involved_watch_only: bool,
+    #[serde(with = "bitcoin::util::amount::serde::as_btc")]
+    pub amount: Amount,
+    pub confirmations: u32,
+    pub label: String,
+}
+
+/// Models an entry of a grouping in the result of "listaddressgroupings"
+///
+/// Bitcoin Core returns the entries as `[address, amount, label]` arrays
+/// where the label is omitted for addresses without one.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct ListAddressGroupingsResultEntry {
+    pub address: Address,
+    #[serde(with = "bitcoin::util::amount::serde::as_btc")]
+    pub amount: Amount,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// Models the result of "listaddressgroupings"
+pub type ListAddressGroupingsResult = Vec<Vec<ListAddressGroupingsResultEntry>>;
+
+/// Models an entry of the result of "getaddressesbylabel"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct GetAddressesByLabelResultEntry {
+    pub purpose: GetAddressInfoResultLabelPurpose,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SignRawTransactionResultError {
@@ -497,6 +791,7 @@ pub enum ScriptPubkeyType {
     NullData,
     Witness_v0_KeyHash,
     Witness_v0_ScriptHash,
+    Witness_v1_Taproot,
     Witness_Unknown,
 }
 
@@ -790,8 +1085,408 @@ pub struct BlockRef {
     pub height: u64,
 }
 
+/// The status of a chain tip in the result of "getchaintips"
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GetChainTipsResultStatus {
+    /// This branch contains at least one invalid block
+    Invalid,
+    /// Not all blocks for this branch are available, but the headers are valid
+    HeadersOnly,
+    /// All blocks are available for this branch, but they were never fully validated
+    ValidHeaders,
+    /// This branch is not part of the active chain, but is fully validated
+    ValidFork,
+    /// This is the tip of the active main chain, which is certainly valid
+    Active,
+}
+
+/// A chain tip in the result of "getchaintips"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct GetChainTipsResultTip {
+    /// The height of the chain tip
+    pub height: u64,
+    /// The block hash of the chain tip
+    pub hash: bitcoin::BlockHash,
+    /// The length of the branch connecting the tip to the main chain,
+    /// zero for the main chain
+    #[serde(rename = "branchlen")]
+    pub branch_length: usize,
+    /// The status of the chain
+    pub status: GetChainTipsResultStatus,
+}
+
+/// Models the result of "getchaintips"
+pub type GetChainTipsResult = Vec<GetChainTipsResultTip>;
+
+/// Models the result of "getblockstats"
+///
+/// All fields are optional since only the requested statistics are returned.
+/// Fee rates are in satoshis per virtual byte.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct GetBlockStatsResult {
+    /// Average fee in the block
+    #[serde(rename = "avgfee", default, with = "bitcoin::util::amount::serde::as_sat::opt")]
+    pub avg_fee: Option<Amount>,
+    /// Average fee rate
+    #[serde(rename = "avgfeerate")]
+    pub avg_fee_rate: Option<u64>,
+    /// Average transaction size
+    #[serde(rename = "avgtxsize")]
+    pub avg_tx_size: Option<u32>,
+    /// The block hash
+    #[serde(rename = "blockhash")]
+    pub block_hash: Option<bitcoin::BlockHash>,
+    /// Fee rates at the 10th, 25th, 50th, 75th, and 90th percentile weight unit
+    #[serde(rename = "feerate_percentiles")]
+    pub fee_rate_percentiles: Option<[u64; 5]>,
+    /// The height of the block
+    pub height: Option<u64>,
+    /// The number of inputs (excluding coinbase)
+    pub ins: Option<usize>,
+    /// Maximum fee in the block
+    #[serde(rename = "maxfee", default, with = "bitcoin::util::amount::serde::as_sat::opt")]
+    pub max_fee: Option<Amount>,
+    /// Maximum fee rate
+    #[serde(rename = "maxfeerate")]
+    pub max_fee_rate: Option<u64>,
+    /// Maximum transaction size
+    #[serde(rename = "maxtxsize")]
+    pub max_tx_size: Option<u32>,
+    /// Truncated median fee in the block
+    #[serde(rename = "medianfee", default, with = "bitcoin::util::amount::serde::as_sat::opt")]
+    pub median_fee: Option<Amount>,
+    /// The block median time past
+    #[serde(rename = "mediantime")]
+    pub median_time: Option<u64>,
+    /// Truncated median transaction size
+    #[serde(rename = "mediantxsize")]
+    pub median_tx_size: Option<u32>,
+    /// Minimum fee in the block
+    #[serde(rename = "minfee", default, with = "bitcoin::util::amount::serde::as_sat::opt")]
+    pub min_fee: Option<Amount>,
+    /// Minimum fee rate
+    #[serde(rename = "minfeerate")]
+    pub min_fee_rate: Option<u64>,
+    /// Minimum transaction size
+    #[serde(rename = "mintxsize")]
+    pub min_tx_size: Option<u32>,
+    /// The number of outputs
+    pub outs: Option<usize>,
+    /// The block subsidy
+    #[serde(default, with = "bitcoin::util::amount::serde::as_sat::opt")]
+    pub subsidy: Option<Amount>,
+    /// Total size of all segwit transactions
+    #[serde(rename = "swtotal_size")]
+    pub sw_total_size: Option<usize>,
+    /// Total weight of all segwit transactions
+    #[serde(rename = "swtotal_weight")]
+    pub sw_total_weight: Option<usize>,
+    /// The number of segwit transactions
+    #[serde(rename = "swtxs")]
+    pub sw_txs: Option<usize>,
+    /// The block time
+    pub time: Option<u64>,
+    /// Total amount in all outputs (excluding coinbase)
+    #[serde(default, with = "bitcoin::util::amount::serde::as_sat::opt")]
+    pub total_out: Option<Amount>,
+    /// Total size of all non-coinbase transactions
+    pub total_size: Option<usize>,
+    /// Total weight of all non-coinbase transactions
+    pub total_weight: Option<usize>,
+    /// The fee total
+    #[serde(rename = "totalfee", default, with = "bitcoin::util::amount::serde::as_sat::opt")]
+    pub total_fee: Option<Amount>,
+    /// The number of transactions (including coinbase)
+    pub txs: Option<usize>,
+    /// The increase/decrease in the number of unspent outputs
+    pub utxo_increase: Option<i32>,
+    /// The increase/decrease in size for the utxo index
+    #[serde(rename = "utxo_size_inc")]
+    pub utxo_size_increase: Option<i32>,
+}
+
+/// Models the result of "getchaintxstats"
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct GetChainTxStatsResult {
+    /// The timestamp for the final block in the window
+    pub time: u64,
+    /// The total number of transactions in the chain up to that point
+    #[serde(rename = "txcount")]
+    pub tx_count: u64,
+    /// The hash of the final block in the window
+    pub window_final_block_hash: bitcoin::BlockHash,
+    /// The height of the final block in the window (Bitcoin Core v0.19+)
+    pub window_final_block_height: Option<u64>,
+    /// The size of the window in number of blocks
+    pub window_block_count: u64,
+    /// The number of transactions in the window, only present if
+    /// `window_block_count` is greater than 0
+    pub window_tx_count: Option<u64>,
+    /// The elapsed time in the window in seconds, only present if
+    /// `window_block_count` is greater than 0
+    pub window_interval: Option<u64>,
+    /// The average rate of transactions per second in the window, only
+    /// present if `window_interval` is greater than 0
+    #[serde(rename = "txrate")]
+    pub tx_rate: Option<f64>,
+}
+
+/// A transaction as listed in the result of "getblocktemplate"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct GetBlockTemplateResultTransaction {
+    /// The transaction id
+    pub txid: bitcoin::Txid,
+    /// The witness transaction id
+    #[serde(rename = "hash")]
+    pub wtxid: bitcoin::Wtxid,
+    /// The raw transaction
+    #[serde(rename = "data", with = "::serde_hex")]
+    pub raw_tx: Vec<u8>,
+    /// The 1-based indices of the transactions in the template this
+    /// transaction depends on
+    pub depends: Vec<u32>,
+    /// The fee paid by the transaction
+    #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+    pub fee: Amount,
+    /// The total sigops cost of the transaction
+    pub sigops: u32,
+    /// The transaction weight
+    pub weight: usize,
+}
+
+impl GetBlockTemplateResultTransaction {
+    pub fn transaction(&self) -> Result<Transaction, encode::Error> {
+        encode::deserialize(&self.raw_tx)
+    }
+}
+
+/// Models the result of "getblocktemplate"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct GetBlockTemplateResult {
+    /// The block version
+    pub version: u32,
+    /// The rules that are to be enforced for the block
+    pub rules: Vec<GetBlockTemplateRules>,
+    /// The client side features supported by the server
+    #[serde(default)]
+    pub capabilities: Vec<GetBlockTemplateCapabilities>,
+    /// The pending, supported version bit deployments
+    #[serde(rename = "vbavailable")]
+    pub version_bits_available: HashMap<String, u32>,
+    /// The version bits the server requires to be set
+    #[serde(rename = "vbrequired")]
+    pub version_bits_required: u32,
+    /// The hash of the current highest block
+    #[serde(rename = "previousblockhash")]
+    pub previous_block_hash: bitcoin::BlockHash,
+    /// The transactions that should be included in the block
+    pub transactions: Vec<GetBlockTemplateResultTransaction>,
+    /// Data that should be included in the coinbase's scriptSig
+    #[serde(rename = "coinbaseaux")]
+    pub coinbase_aux: HashMap<String, String>,
+    /// The maximum allowable input to the coinbase transaction, including
+    /// the block subsidy and the fees of all template transactions
+    #[serde(rename = "coinbasevalue", with = "bitcoin::util::amount::serde::as_sat")]
+    pub coinbase_value: Amount,
+    /// The hash target
+    #[serde(with = "::serde_hex")]
+    pub target: Vec<u8>,
+    /// The minimum timestamp appropriate for the next block
+    #[serde(rename = "mintime")]
+    pub min_time: u64,
+    /// The ways the template may be changed
+    pub mutable: Vec<GetBlockTemplateMutations>,
+    /// The range of valid nonces
+    #[serde(rename = "noncerange", with = "::serde_hex")]
+    pub nonce_range: Vec<u8>,
+    /// The limit of sigops in blocks
+    #[serde(rename = "sigoplimit")]
+    pub sigop_limit: u32,
+    /// The limit of block size
+    #[serde(rename = "sizelimit")]
+    pub size_limit: u32,
+    /// The limit of block weight
+    #[serde(rename = "weightlimit")]
+    pub weight_limit: u32,
+    /// The current timestamp
+    #[serde(rename = "curtime")]
+    pub current_time: u64,
+    /// The compressed target of the block
+    #[serde(with = "::serde_hex")]
+    pub bits: Vec<u8>,
+    /// The height of the block
+    pub height: u64,
+    /// The witness commitment for the template transactions, if segwit is
+    /// active
+    pub default_witness_commitment: Option<Script>,
+}
+
+/// The block rules reported by and requested from "getblocktemplate"
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GetBlockTemplateRules {
+    #[serde(alias = "!segwit")]
+    Segwit,
+    #[serde(alias = "!signet")]
+    Signet,
+    Csv,
+    Taproot,
+    /// A rule this library doesn't know, like a future deployment. Not to be
+    /// requested.
+    #[serde(other)]
+    Other,
+}
+
+/// The ways a template returned by "getblocktemplate" may be changed
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GetBlockTemplateMutations {
+    Time,
+    Transactions,
+    #[serde(rename = "prevblock")]
+    PreviousBlock,
+}
+
 // Custom types for input arguments.
 
+/// The mode argument of "getblocktemplate"
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GetBlockTemplateModes {
+    Template,
+    Proposal,
+}
+
+/// The client side features that can be announced to "getblocktemplate"
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GetBlockTemplateCapabilities {
+    LongPoll,
+    CoinbaseTxn,
+    CoinbaseValue,
+    Proposal,
+    ServerList,
+    WorkId,
+}
+
+/// A scan object for "scantxoutset".
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum ScanTxOutRequest {
+    /// A descriptor that is not ranged.
+    Single(String),
+    /// A ranged descriptor with the range of child indices to scan.
+    Extended {
+        desc: String,
+        range: (u64, u64),
+    },
+}
+
+impl ScanTxOutRequest {
+    /// Scan for the outputs paying to `address`.
+    pub fn address(address: &Address) -> ScanTxOutRequest {
+        ScanTxOutRequest::Single(format!("addr({})", address))
+    }
+
+    /// Scan for the outputs matching `descriptor`.
+    pub fn descriptor<S: Into<String>>(descriptor: S) -> ScanTxOutRequest {
+        ScanTxOutRequest::Single(descriptor.into())
+    }
+
+    /// Scan for the outputs matching the children of a ranged `descriptor`
+    /// with indices `start` to `end` (inclusive).
+    pub fn ranged<S: Into<String>>(descriptor: S, start: u64, end: u64) -> ScanTxOutRequest {
+        ScanTxOutRequest::Extended {
+            desc: descriptor.into(),
+            range: (start, end),
+        }
+    }
+}
+
+/// Used to refer to a block either by its hash or by its height.
+#[derive(Serialize, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[serde(untagged)]
+pub enum HashOrHeight {
+    BlockHash(bitcoin::BlockHash),
+    Height(u64),
+}
+
+impl From<bitcoin::BlockHash> for HashOrHeight {
+    fn from(hash: bitcoin::BlockHash) -> HashOrHeight {
+        HashOrHeight::BlockHash(hash)
+    }
+}
+
+impl From<u64> for HashOrHeight {
+    fn from(height: u64) -> HashOrHeight {
+        HashOrHeight::Height(height)
+    }
+}
+
+/// The statistics that can be selected in "getblockstats".
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum BlockStatsFields {
+    #[serde(rename = "avgfee")]
+    AverageFee,
+    #[serde(rename = "avgfeerate")]
+    AverageFeeRate,
+    #[serde(rename = "avgtxsize")]
+    AverageTxSize,
+    #[serde(rename = "blockhash")]
+    BlockHash,
+    #[serde(rename = "feerate_percentiles")]
+    FeeRatePercentiles,
+    #[serde(rename = "height")]
+    Height,
+    #[serde(rename = "ins")]
+    Ins,
+    #[serde(rename = "maxfee")]
+    MaxFee,
+    #[serde(rename = "maxfeerate")]
+    MaxFeeRate,
+    #[serde(rename = "maxtxsize")]
+    MaxTxSize,
+    #[serde(rename = "medianfee")]
+    MedianFee,
+    #[serde(rename = "mediantime")]
+    MedianTime,
+    #[serde(rename = "mediantxsize")]
+    MedianTxSize,
+    #[serde(rename = "minfee")]
+    MinFee,
+    #[serde(rename = "minfeerate")]
+    MinFeeRate,
+    #[serde(rename = "mintxsize")]
+    MinTxSize,
+    #[serde(rename = "outs")]
+    Outs,
+    #[serde(rename = "subsidy")]
+    Subsidy,
+    #[serde(rename = "swtotal_size")]
+    SegWitTotalSize,
+    #[serde(rename = "swtotal_weight")]
+    SegWitTotalWeight,
+    #[serde(rename = "swtxs")]
+    SegWitTxs,
+    #[serde(rename = "time")]
+    Time,
+    #[serde(rename = "total_out")]
+    TotalOut,
+    #[serde(rename = "total_size")]
+    TotalSize,
+    #[serde(rename = "total_weight")]
+    TotalWeight,
+    #[serde(rename = "totalfee")]
+    TotalFee,
+    #[serde(rename = "txs")]
+    Txs,
+    #[serde(rename = "utxo_increase")]
+    UtxoIncrease,
+    #[serde(rename = "utxo_size_inc")]
+    UtxoSizeIncrease,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq, Hash)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum EstimateMode {
@@ -878,6 +1573,150 @@ impl FundRawTransactionResult {
     }
 }
 
+/// Options for "sendtoaddress".
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SendToAddressOptions {
+    /// A comment used to store what the transaction is for, stored in the
+    /// wallet only.
+    pub comment: Option<String>,
+    /// A comment to store the name of the person or organization to which
+    /// you're sending the transaction, stored in the wallet only.
+    pub comment_to: Option<String>,
+    /// Deduct the fee from the amount being sent.
+    pub subtract_fee_from_amount: Option<bool>,
+    /// Allow this transaction to be replaced by a transaction with higher
+    /// fees via BIP 125.
+    pub replaceable: Option<bool>,
+    /// Confirmation target in blocks.
+    pub conf_target: Option<u32>,
+    /// The fee estimate mode.
+    pub estimate_mode: Option<EstimateMode>,
+    /// Avoid spending from dirty addresses (Bitcoin Core v0.19+).
+    pub avoid_reuse: Option<bool>,
+    /// The fee rate in sat/vB (Bitcoin Core v0.21+).
+    pub fee_rate: Option<f64>,
+}
+
+/// Options for "sendmany".
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SendManyOptions {
+    /// A comment, stored in the wallet only.
+    pub comment: Option<String>,
+    /// The addresses to equally deduct the fee from.
+    pub subtract_fee_from: Vec<Address>,
+    /// Allow this transaction to be replaced by a transaction with higher
+    /// fees via BIP 125.
+    pub replaceable: Option<bool>,
+    /// Confirmation target in blocks.
+    pub conf_target: Option<u32>,
+    /// The fee estimate mode.
+    pub estimate_mode: Option<EstimateMode>,
+    /// The fee rate in sat/vB (Bitcoin Core v0.21+).
+    pub fee_rate: Option<f64>,
+}
+
+/// Models the result of "sendtoaddress" and "sendmany" when `verbose` is set
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct SendVerboseResult {
+    /// The transaction id
+    pub txid: bitcoin::Txid,
+    /// The transaction fee reason
+    pub fee_reason: String,
+}
+
+/// An output for "send".
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SendOutput {
+    /// Pay the amount to the address.
+    Address(Address, Amount),
+    /// An OP_RETURN output with the data.
+    Data(Vec<u8>),
+}
+
+impl serde::Serialize for SendOutput {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(1))?;
+        match *self {
+            SendOutput::Address(ref address, amount) => {
+                map.serialize_entry(&address.to_string(), &amount.as_btc())?
+            }
+            SendOutput::Data(ref data) => map.serialize_entry("data", &data.to_hex())?,
+        }
+        map.end()
+    }
+}
+
+/// Options for "send".
+#[derive(Serialize, Clone, PartialEq, Debug, Default)]
+pub struct SendOptions {
+    /// Automatically include coins from the wallet to cover the target
+    /// amount.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub add_inputs: Option<bool>,
+    /// Add the transaction to the wallet and broadcast it; otherwise, return
+    /// the hex.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub add_to_wallet: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_address: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_position: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_type: Option<AddressType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conf_target: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimate_mode: Option<EstimateMode>,
+    /// The fee rate in sat/vB.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_watching: Option<bool>,
+    /// Specify inputs instead of adding them automatically.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<CreateRawTransactionInput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locktime: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lock_unspents: Option<bool>,
+    /// Always return a PSBT, implies `add_to_wallet` to be false.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub psbt: Option<bool>,
+    /// The output indices to equally deduct the fee from.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subtract_fee_from_outputs: Vec<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replaceable: Option<bool>,
+}
+
+/// Models the result of "send"
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct SendResult {
+    /// Whether the transaction is complete
+    pub complete: bool,
+    /// The transaction id, only present if the transaction is complete
+    pub txid: Option<bitcoin::Txid>,
+    /// The raw transaction, only present if `add_to_wallet` was false and
+    /// the transaction is complete
+    #[serde(default, with = "::serde_hex::opt")]
+    pub hex: Option<Vec<u8>>,
+    /// The base64-encoded PSBT, only present if the transaction is
+    /// incomplete or if `psbt` was set
+    pub psbt: Option<String>,
+}
+
+impl SendResult {
+    /// The raw transaction, if present.
+    pub fn transaction(&self) -> Option<Result<Transaction, encode::Error>> {
+        self.hex.as_ref().map(|hex| encode::deserialize(hex))
+    }
+}
+
 // Used for signrawtransaction argument.
 #[derive(Serialize, Clone, PartialEq, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -1106,6 +1945,36 @@ mod tests {
         assert_eq!(expected, serde_json::from_str(json).unwrap());
     }
 
+    #[test]
+    fn test_GetBlockTemplateRules() {
+        let rules: Vec<GetBlockTemplateRules> =
+            serde_json::from_str(r#"["csv", "!segwit", "taproot", "testdummy"]"#).unwrap();
+        assert_eq!(
+            rules,
+            vec![
+                GetBlockTemplateRules::Csv,
+                GetBlockTemplateRules::Segwit,
+                GetBlockTemplateRules::Taproot,
+                GetBlockTemplateRules::Other,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_RescanBlockchainResult() {
+        let expected = RescanBlockchainResult {
+            start_height: 100,
+            stop_height: Some(200),
+        };
+        let json = r#"{"start_height": 100, "stop_height": 200}"#;
+        assert_eq!(expected, serde_json::from_str(json).unwrap());
+
+        // An aborted rescan has no stop height.
+        let json = r#"{"start_height": 100, "stop_height": null}"#;
+        let result: RescanBlockchainResult = serde_json::from_str(json).unwrap();
+        assert_eq!(result.stop_height, None);
+    }
+
     #[test]
     fn test_GetMiningInfoResult() {
         let expected = GetMiningInfoResult {
@@ -1177,7 +2046,7 @@ mod tests {
                 }),
                 sequence: 4294967294,
                 txinwitness: None,
-
+                prevout: None,
             }],
             vout: vec![GetRawTransactionResultVout{
                 value: Amount::from_btc(44.98834461).unwrap(),
@@ -1188,6 +2057,7 @@ mod tests {
                     req_sigs: Some(1),
                     type_: Some(ScriptPubkeyType::PubkeyHash),
                     addresses: Some(vec![addr!("n3wk1KcFnVibGdqQa6jbwoR8gbVtRbYM4M")]),
+                    address: None,
                 },
             }, GetRawTransactionResultVout{
                 value: Amount::from_btc(1.0).unwrap(),
@@ -1198,6 +2068,7 @@ mod tests {
                     req_sigs: Some(1),
                     type_: Some(ScriptPubkeyType::PubkeyHash),
                     addresses: Some(vec![addr!("mq3VuL2K63VKWkp8vvqRiJPre4h9awrHfA")]),
+                    address: None,
                 },
             }],
             blockhash: Some(from_hex!("00000000000000039dc06adbd7666a8d1df9acf9d0329d73651b764167d63765")),
@@ -1339,6 +2210,7 @@ mod tests {
                 req_sigs: Some(1),
                 type_: Some(ScriptPubkeyType::PubkeyHash),
                 addresses: Some(vec![addr!("mq3VuL2K63VKWkp8vvqRiJPre4h9awrHfA")]),
+                address: None,
             },
             coinbase: false,
         };
@@ -1400,6 +2272,372 @@ mod tests {
         assert_eq!(expected, serde_json::from_str(json).unwrap());
     }
 
+    #[test]
+    fn test_GetChainTipsResult() {
+        let expected = vec![
+            GetChainTipsResultTip {
+                height: 1779541,
+                hash: from_hex!("000000000000003b1eba5ecf4cc46e6cce7d6c9b6fd3e4bc3ab9a4f0e5e9e7ba"),
+                branch_length: 0,
+                status: GetChainTipsResultStatus::Active,
+            },
+            GetChainTipsResultTip {
+                height: 1779470,
+                hash: from_hex!("0000000000000021a68cf7b2e30e4b6e0b5a8fe0c5e3f64e1fbc6e8fc2c38a13"),
+                branch_length: 1,
+                status: GetChainTipsResultStatus::ValidFork,
+            },
+            GetChainTipsResultTip {
+                height: 1384958,
+                hash: from_hex!("00000000000000039dc06adbd7666a8d1df9acf9d0329d73651b764167d63765"),
+                branch_length: 3,
+                status: GetChainTipsResultStatus::HeadersOnly,
+            },
+        ];
+        let json = r#"
+            [
+              {
+                "height": 1779541,
+                "hash": "000000000000003b1eba5ecf4cc46e6cce7d6c9b6fd3e4bc3ab9a4f0e5e9e7ba",
+                "branchlen": 0,
+                "status": "active"
+              },
+              {
+                "height": 1779470,
+                "hash": "0000000000000021a68cf7b2e30e4b6e0b5a8fe0c5e3f64e1fbc6e8fc2c38a13",
+                "branchlen": 1,
+                "status": "valid-fork"
+              },
+              {
+                "height": 1384958,
+                "hash": "00000000000000039dc06adbd7666a8d1df9acf9d0329d73651b764167d63765",
+                "branchlen": 3,
+                "status": "headers-only"
+              }
+            ]
+        "#;
+        let result: GetChainTipsResult = serde_json::from_str(json).unwrap();
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_GetBlockStatsResult() {
+        let expected = GetBlockStatsResult {
+            avg_fee: Some(Amount::from_sat(14360)),
+            avg_fee_rate: Some(51),
+            avg_tx_size: Some(371),
+            block_hash: Some(from_hex!("0000000000000000000e4d1c4a0c4bc3e5b5fc3d8ac5c2a8fc7e9a3b2d3e3c4a")),
+            fee_rate_percentiles: Some([10, 21, 43, 60, 113]),
+            height: Some(630000),
+            ins: Some(6830),
+            max_fee: Some(Amount::from_sat(2049000)),
+            max_fee_rate: Some(1370),
+            max_tx_size: Some(39926),
+            median_fee: Some(Amount::from_sat(8064)),
+            median_time: Some(1589225023),
+            median_tx_size: Some(249),
+            min_fee: Some(Amount::from_sat(266)),
+            min_fee_rate: Some(1),
+            min_tx_size: Some(189),
+            outs: Some(5753),
+            subsidy: Some(Amount::from_sat(625000000)),
+            sw_total_size: Some(655318),
+            sw_total_weight: Some(1811407),
+            sw_txs: Some(2201),
+            time: Some(1589225023),
+            total_out: Some(Amount::from_sat(2184655802347)),
+            total_size: Some(1179232),
+            total_weight: Some(3993421),
+            total_fee: Some(Amount::from_sat(45633327)),
+            txs: Some(3179),
+            utxo_increase: Some(-1077),
+            utxo_size_increase: Some(-73016),
+        };
+        let json = r#"
+            {
+              "avgfee": 14360,
+              "avgfeerate": 51,
+              "avgtxsize": 371,
+              "blockhash": "0000000000000000000e4d1c4a0c4bc3e5b5fc3d8ac5c2a8fc7e9a3b2d3e3c4a",
+              "feerate_percentiles": [10, 21, 43, 60, 113],
+              "height": 630000,
+              "ins": 6830,
+              "maxfee": 2049000,
+              "maxfeerate": 1370,
+              "maxtxsize": 39926,
+              "medianfee": 8064,
+              "mediantime": 1589225023,
+              "mediantxsize": 249,
+              "minfee": 266,
+              "minfeerate": 1,
+              "mintxsize": 189,
+              "outs": 5753,
+              "subsidy": 625000000,
+              "swtotal_size": 655318,
+              "swtotal_weight": 1811407,
+              "swtxs": 2201,
+              "time": 1589225023,
+              "total_out": 2184655802347,
+              "total_size": 1179232,
+              "total_weight": 3993421,
+              "totalfee": 45633327,
+              "txs": 3179,
+              "utxo_increase": -1077,
+              "utxo_size_inc": -73016
+            }
+        "#;
+        assert_eq!(expected, serde_json::from_str(json).unwrap());
+
+        let filtered: GetBlockStatsResult = serde_json::from_str(r#"{"height": 630000}"#).unwrap();
+        assert_eq!(filtered.height, Some(630000));
+        assert_eq!(filtered.avg_fee, None);
+        assert_eq!(filtered.fee_rate_percentiles, None);
+    }
+
+    #[test]
+    fn test_ScanTxOutResult() {
+        let expected = ScanTxOutResult {
+            success: Some(true),
+            tx_outs: Some(65079925),
+            height: Some(1779541),
+            best_block: Some(from_hex!("000000000000003b1eba5ecf4cc46e6cce7d6c9b6fd3e4bc3ab9a4f0e5e9e7ba")),
+            unspents: vec![ScanTxOutResultUnspent {
+                txid: from_hex!("4a5b5266e1750488395ac15c0376c9d48abf45e4df620777fe8cff096f57aa91"),
+                vout: 1,
+                script_pub_key: script!("76a914687ffeffe8cf4e4c038da46a9b1d37db385a472d88ac"),
+                descriptor: Some("addr(mq3VuL2K63VKWkp8vvqRiJPre4h9awrHfA)#3r3kxt3m".into()),
+                amount: Amount::from_btc(1.0).unwrap(),
+                height: 1384958,
+            }],
+            total_amount: Amount::from_btc(1.0).unwrap(),
+        };
+        let json = r#"
+            {
+              "success": true,
+              "txouts": 65079925,
+              "height": 1779541,
+              "bestblock": "000000000000003b1eba5ecf4cc46e6cce7d6c9b6fd3e4bc3ab9a4f0e5e9e7ba",
+              "unspents": [
+                {
+                  "txid": "4a5b5266e1750488395ac15c0376c9d48abf45e4df620777fe8cff096f57aa91",
+                  "vout": 1,
+                  "scriptPubKey": "76a914687ffeffe8cf4e4c038da46a9b1d37db385a472d88ac",
+                  "desc": "addr(mq3VuL2K63VKWkp8vvqRiJPre4h9awrHfA)#3r3kxt3m",
+                  "amount": 1.00000000,
+                  "height": 1384958
+                }
+              ],
+              "total_amount": 1.00000000
+            }
+        "#;
+        assert_eq!(expected, serde_json::from_str(json).unwrap());
+
+        let requests = vec![
+            ScanTxOutRequest::address(&addr!("mq3VuL2K63VKWkp8vvqRiJPre4h9awrHfA")),
+            ScanTxOutRequest::ranged("wpkh(tpubD6NzVbkrYhZ4WaWSyoBvQwbpLkojyoTZPRsgXELWz3Popb3qkjcJyJUGLnL4qHHoQvao8ESaAstxYSnhyswJ76uZPStJRJCTKvosUCJZL5B/0/*)", 0, 100),
+        ];
+        assert_eq!(
+            serde_json::to_string(&requests).unwrap(),
+            r#"["addr(mq3VuL2K63VKWkp8vvqRiJPre4h9awrHfA)",{"desc":"wpkh(tpubD6NzVbkrYhZ4WaWSyoBvQwbpLkojyoTZPRsgXELWz3Popb3qkjcJyJUGLnL4qHHoQvao8ESaAstxYSnhyswJ76uZPStJRJCTKvosUCJZL5B/0/*)","range":[0,100]}]"#
+        );
+    }
+
+    #[test]
+    fn test_SendOutput() {
+        let outputs = vec![
+            SendOutput::Address(addr!("mq3VuL2K63VKWkp8vvqRiJPre4h9awrHfA"), Amount::from_sat(150000)),
+            SendOutput::Data(hex!("deadbeef")),
+        ];
+        assert_eq!(
+            serde_json::to_string(&outputs).unwrap(),
+            r#"[{"mq3VuL2K63VKWkp8vvqRiJPre4h9awrHfA":0.0015},{"data":"deadbeef"}]"#
+        );
+    }
+
+    #[test]
+    fn test_ListAddressGroupingsResult() {
+        let expected = vec![vec![
+            ListAddressGroupingsResultEntry {
+                address: addr!("mq3VuL2K63VKWkp8vvqRiJPre4h9awrHfA"),
+                amount: Amount::from_sat(150000),
+                label: Some("customer".into()),
+            },
+            ListAddressGroupingsResultEntry {
+                address: addr!("mrcNu71ztWjAQA6ww9kHiW3zBWSQidHXTQ"),
+                amount: Amount::from_sat(0),
+                label: None,
+            },
+        ]];
+        let json = r#"
+            [
+              [
+                ["mq3VuL2K63VKWkp8vvqRiJPre4h9awrHfA", 0.00150000, "customer"],
+                ["mrcNu71ztWjAQA6ww9kHiW3zBWSQidHXTQ", 0.00000000]
+              ]
+            ]
+        "#;
+        assert_eq!(expected, serde_json::from_str::<ListAddressGroupingsResult>(json).unwrap());
+    }
+
+    #[test]
+    fn test_DecodeScriptResult() {
+        let expected = DecodeScriptResult {
+            asm: "OP_DUP OP_HASH160 751e76e8199196d454941c45d1b3a323f1433bd6 OP_EQUALVERIFY OP_CHECKSIG".into(),
+            hex: None,
+            type_: Some(ScriptPubkeyType::PubkeyHash),
+            req_sigs: Some(1),
+            addresses: Some(vec![addr!("mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r")]),
+            p2sh: Some(addr!("2NByiBUaEXrhmqAsg7BbLpcQSAQs1EDwt5w")),
+            segwit: Some(DecodeScriptResultSegwit {
+                asm: "0 751e76e8199196d454941c45d1b3a323f1433bd6".into(),
+                hex: hex!("0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+                type_: Some(ScriptPubkeyType::Witness_v0_KeyHash),
+                req_sigs: Some(1),
+                addresses: Some(vec![addr!("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")]),
+                p2sh_segwit: Some(addr!("2NAUYAHhujozruyzpsFRP63mbrdaU5wnEpN")),
+            }),
+        };
+        let json = r#"
+            {
+              "asm": "OP_DUP OP_HASH160 751e76e8199196d454941c45d1b3a323f1433bd6 OP_EQUALVERIFY OP_CHECKSIG",
+              "reqSigs": 1,
+              "type": "pubkeyhash",
+              "addresses": [
+                "mrCDrCybB6J1vRfbwM5hemdJz73FwDBC8r"
+              ],
+              "p2sh": "2NByiBUaEXrhmqAsg7BbLpcQSAQs1EDwt5w",
+              "segwit": {
+                "asm": "0 751e76e8199196d454941c45d1b3a323f1433bd6",
+                "hex": "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+                "reqSigs": 1,
+                "type": "witness_v0_keyhash",
+                "addresses": [
+                  "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080"
+                ],
+                "p2sh-segwit": "2NAUYAHhujozruyzpsFRP63mbrdaU5wnEpN"
+              }
+            }
+        "#;
+        assert_eq!(expected, serde_json::from_str(json).unwrap());
+    }
+
+    #[test]
+    fn test_GetBlockVerboseTxResult() {
+        // Block 80000 in the format returned with verbosity 3 by Bitcoin Core v23.
+        let json = r#"
+            {
+              "hash": "000000000043a8c0fd1d6f726790caa2a406010d19efd2780db27bdbbd93baf6",
+              "confirmations": 600000,
+              "height": 80000,
+              "version": 1,
+              "versionHex": "00000001",
+              "merkleroot": "8fb300e3fdb6f30a4c67233b997f99fdd518b968b9a3fd65857bfe78b2600719",
+              "time": 1284613427,
+              "mediantime": 1284610953,
+              "nonce": 1462756097,
+              "bits": "1b5bede6",
+              "difficulty": 3091.736890411376,
+              "chainwork": "00000000000000000000000000000000000000000000000000c0a1d3b1b0e3e7",
+              "nTx": 2,
+              "previousblockhash": "00000000001937917bd2caba204bb1aa530ec1de9d0f6736e5d85d96da9c8bba",
+              "nextblockhash": "00000000000036312a44ab7711afa46f475913fbd9727cf508ed4af3bc933d16",
+              "strippedsize": 292,
+              "size": 292,
+              "weight": 1168,
+              "tx": [
+                {
+                  "txid": "c06fbab289f723c6261d3030ddb6be121f7d2508d77862bb1e484f5cd7f92b25",
+                  "hash": "c06fbab289f723c6261d3030ddb6be121f7d2508d77862bb1e484f5cd7f92b25",
+                  "version": 1,
+                  "size": 134,
+                  "vsize": 134,
+                  "weight": 536,
+                  "locktime": 0,
+                  "vin": [
+                    {
+                      "coinbase": "04e6ed5b1b014e",
+                      "sequence": 4294967295
+                    }
+                  ],
+                  "vout": [
+                    {
+                      "value": 50.00000000,
+                      "n": 0,
+                      "scriptPubKey": {
+                        "asm": "04b68a50eaa0287eff855189f949c1c6e5f58b37c88231373d8a59809cbae83059cc6469d65c665ccfd1cfeb75c6e8e19413bba7fbff9bc762419a76d87b16086e OP_CHECKSIG",
+                        "hex": "4104b68a50eaa0287eff855189f949c1c6e5f58b37c88231373d8a59809cbae83059cc6469d65c665ccfd1cfeb75c6e8e19413bba7fbff9bc762419a76d87b16086eac",
+                        "type": "pubkey"
+                      }
+                    }
+                  ],
+                  "hex": "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0704e6ed5b1b014effffffff0100f2052a01000000434104b68a50eaa0287eff855189f949c1c6e5f58b37c88231373d8a59809cbae83059cc6469d65c665ccfd1cfeb75c6e8e19413bba7fbff9bc762419a76d87b16086eac00000000"
+                },
+                {
+                  "txid": "5a4ebf66822b0b2d56bd9dc64ece0bc38ee7844a23ff1d7320a88c5fdb2ad3e2",
+                  "hash": "5a4ebf66822b0b2d56bd9dc64ece0bc38ee7844a23ff1d7320a88c5fdb2ad3e2",
+                  "version": 1,
+                  "size": 158,
+                  "vsize": 158,
+                  "weight": 632,
+                  "locktime": 0,
+                  "vin": [
+                    {
+                      "txid": "f5d8ee39a430901c91a5917b9f2dc19d6d1a0e9cea205b009ca73dd04470b9a6",
+                      "vout": 0,
+                      "scriptSig": {
+                        "asm": "304502206e21798a42fae0e854281abd38bacd1aeed3ee3738d9e1446618c4571d1090db022100e2ac980643b0b82c0e88ffdfec6b64e3e6ba35e7ba5fdd7d5d6cc8d25c6b2415[ALL]",
+                        "hex": "48304502206e21798a42fae0e854281abd38bacd1aeed3ee3738d9e1446618c4571d1090db022100e2ac980643b0b82c0e88ffdfec6b64e3e6ba35e7ba5fdd7d5d6cc8d25c6b241501"
+                      },
+                      "prevout": {
+                        "generated": true,
+                        "height": 79961,
+                        "value": 50.00000000,
+                        "scriptPubKey": {
+                          "asm": "04b68a50eaa0287eff855189f949c1c6e5f58b37c88231373d8a59809cbae83059cc6469d65c665ccfd1cfeb75c6e8e19413bba7fbff9bc762419a76d87b16086e OP_CHECKSIG",
+                          "hex": "4104b68a50eaa0287eff855189f949c1c6e5f58b37c88231373d8a59809cbae83059cc6469d65c665ccfd1cfeb75c6e8e19413bba7fbff9bc762419a76d87b16086eac",
+                          "type": "pubkey"
+                        }
+                      },
+                      "sequence": 4294967295
+                    }
+                  ],
+                  "vout": [
+                    {
+                      "value": 50.00000000,
+                      "n": 0,
+                      "scriptPubKey": {
+                        "asm": "OP_DUP OP_HASH160 404371705fa9bd789a2fcd52d2c580b65d35549d OP_EQUALVERIFY OP_CHECKSIG",
+                        "hex": "76a914404371705fa9bd789a2fcd52d2c580b65d35549d88ac",
+                        "address": "16ro3Jptwo4asSevZnsRX6vfRS24TGE6uK",
+                        "type": "pubkeyhash"
+                      }
+                    }
+                  ],
+                  "fee": 0.00000000,
+                  "hex": "0100000001a6b97044d03da79c005b20ea9c0e1a6d9dc12d9f7b91a5911c9030a439eed8f5000000004948304502206e21798a42fae0e854281abd38bacd1aeed3ee3738d9e1446618c4571d1090db022100e2ac980643b0b82c0e88ffdfec6b64e3e6ba35e7ba5fdd7d5d6cc8d25c6b241501ffffffff0100f2052a010000001976a914404371705fa9bd789a2fcd52d2c580b65d35549d88ac00000000"
+                }
+              ]
+            }
+        "#;
+        let block: GetBlockVerboseTxResult = serde_json::from_str(json).unwrap();
+
+        assert_eq!(block.tx.len(), 2);
+        assert!(block.tx[0].is_coinbase());
+        assert_eq!(block.tx[0].fee, None);
+        assert_eq!(block.tx[0].transaction().unwrap().txid(), block.tx[0].txid);
+        assert_eq!(block.tx[1].transaction().unwrap().txid(), block.tx[1].txid);
+        assert_eq!(block.total_fee(), Some(Amount::ZERO));
+
+        let prevout = block.tx[1].vin[0].prevout.as_ref().unwrap();
+        assert!(prevout.generated);
+        assert_eq!(prevout.value, Amount::from_btc(50.0).unwrap());
+        assert_eq!(prevout.script_pub_key.type_, Some(ScriptPubkeyType::Pubkey));
+        assert_eq!(
+            block.tx[1].vout[0].script_pub_key.address,
+            Some(addr!("16ro3Jptwo4asSevZnsRX6vfRS24TGE6uK"))
+        );
+    }
+
     //TODO(stevenroose) test SignRawTransactionResult
 
     //TODO(stevenroose) test UTXO